@@ -0,0 +1,263 @@
+use crate::{
+    normalize_tags, validate_attachments, Attachment, NetworkingError,
+    ProfessionalNetworkingInstruction, UserProfile, LOG_SCHEMA_VERSION, NFT_FRIEND_THRESHOLD,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use std::collections::{HashMap, HashSet};
+
+// Аккаунты, которые нужны AcceptFriendRequest, если принятие доводит пользователя до порога NFT
+#[derive(Debug, Clone, Copy)]
+pub struct NftAccounts {
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+}
+
+// Высокоуровневое действие пользователя
+#[derive(Debug, Clone)]
+pub enum Action {
+    CreateUserProfile {
+        user: Pubkey,
+        name: String,
+        bio: String,
+        profile_picture: String,
+        referred_by: Option<Pubkey>,
+    },
+    SendFriendRequest {
+        user: Pubkey,
+        friend: Pubkey,
+    },
+    AcceptFriendRequest {
+        user: Pubkey,
+        friend: Pubkey,
+        nft: Option<NftAccounts>,
+    },
+    WritePost {
+        user: Pubkey,
+        content: String,
+        tags: Vec<String>,
+        attachments: Vec<Attachment>,
+    },
+    AddComment {
+        user: Pubkey,
+        post_author: Pubkey,
+        post_index: usize,
+        content: String,
+    },
+}
+
+// Ошибка сборки пакета: индекс действия и причина
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchError {
+    pub index: usize,
+    pub error: ProgramError,
+}
+
+// Состояние профилей, которое пакет видит по ходу выполнения
+#[derive(Default)]
+struct SimulatedProfile {
+    friends: HashSet<Pubkey>,
+    nft_owned: bool,
+    posts: HashMap<Pubkey, usize>,
+}
+
+impl SimulatedProfile {
+    fn eligible(&self) -> bool {
+        self.nft_owned && self.friends.len() >= NFT_FRIEND_THRESHOLD
+    }
+}
+
+// Проверяет, что действия можно выполнить именно в этом порядке, и собирает инструкции
+// с правильными аккаунтами. `existing` — профили, уже созданные в сети,
+// `payer` оплачивает все создаваемые аккаунты.
+pub fn build_instructions(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    existing: &HashMap<Pubkey, UserProfile>,
+    actions: &[Action],
+) -> Result<Vec<Instruction>, BatchError> {
+    let mut profiles: HashMap<Pubkey, SimulatedProfile> = existing
+        .iter()
+        .map(|(key, profile)| {
+            let simulated = SimulatedProfile {
+                friends: profile.friends.clone(),
+                nft_owned: profile.nft_owned,
+                posts: profile
+                    .posts
+                    .iter()
+                    .map(|(author, posts)| (*author, posts.len()))
+                    .collect(),
+            };
+            (*key, simulated)
+        })
+        .collect();
+
+    actions
+        .iter()
+        .enumerate()
+        .map(|(index, action)| {
+            build_instruction(program_id, payer, &mut profiles, action)
+                .map_err(|error| BatchError { index, error })
+        })
+        .collect()
+}
+
+fn build_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    profiles: &mut HashMap<Pubkey, SimulatedProfile>,
+    action: &Action,
+) -> Result<Instruction, ProgramError> {
+    let (data, accounts) = match action.clone() {
+        Action::CreateUserProfile {
+            user,
+            name,
+            bio,
+            profile_picture,
+            referred_by,
+        } => {
+            if referred_by == Some(user) {
+                return Err(NetworkingError::SelfReferral.into());
+            }
+            if profiles.contains_key(&user) {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            profiles.insert(user, SimulatedProfile::default());
+            (
+                ProfessionalNetworkingInstruction::CreateUserProfile {
+                    name,
+                    bio,
+                    profile_picture,
+                    referred_by,
+                },
+                vec![AccountMeta::new(user, true)],
+            )
+        }
+
+        Action::SendFriendRequest { user, friend } => {
+            let profile = profiles
+                .get_mut(&user)
+                .ok_or(ProgramError::UninitializedAccount)?;
+            if !profile.friends.insert(friend) {
+                return Err(NetworkingError::AlreadyFriends.into());
+            }
+            (
+                ProfessionalNetworkingInstruction::SendFriendRequest {
+                    friend_address: friend,
+                },
+                vec![AccountMeta::new(user, true)],
+            )
+        }
+
+        Action::AcceptFriendRequest { user, friend, nft } => {
+            if !profiles.contains_key(&friend) {
+                return Err(ProgramError::UninitializedAccount);
+            }
+            let profile = profiles
+                .get_mut(&user)
+                .ok_or(ProgramError::UninitializedAccount)?;
+            profile.friends.insert(friend);
+
+            let mut accounts = vec![AccountMeta::new(user, true)];
+            if profile.friends.len() >= NFT_FRIEND_THRESHOLD && !profile.nft_owned {
+                let nft = nft.ok_or(ProgramError::NotEnoughAccountKeys)?;
+                accounts.extend([
+                    AccountMeta::new(nft.mint, true),
+                    AccountMeta::new(nft.token_account, true),
+                    AccountMeta::new(*payer, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(spl_token::id(), false),
+                    AccountMeta::new_readonly(sysvar::rent::id(), false),
+                ]);
+                profile.nft_owned = true;
+            }
+            accounts.push(AccountMeta::new(friend, false));
+
+            if let Some(friend_profile) = profiles.get_mut(&friend) {
+                friend_profile.friends.insert(user);
+            }
+            (
+                ProfessionalNetworkingInstruction::AcceptFriendRequest {
+                    friend_address: friend,
+                },
+                accounts,
+            )
+        }
+
+        Action::WritePost {
+            user,
+            content,
+            tags,
+            attachments,
+        } => {
+            let profile = profiles
+                .get_mut(&user)
+                .ok_or(ProgramError::UninitializedAccount)?;
+            if !profile.eligible() {
+                return Err(NetworkingError::NotEligible.into());
+            }
+            let tags = normalize_tags(tags)?;
+            validate_attachments(&attachments)?;
+            *profile.posts.entry(user).or_default() += 1;
+            (
+                ProfessionalNetworkingInstruction::WritePost {
+                    content,
+                    tags,
+                    attachments,
+                },
+                vec![AccountMeta::new(user, true)],
+            )
+        }
+
+        Action::AddComment {
+            user,
+            post_author,
+            post_index,
+            content,
+        } => {
+            let profile = profiles
+                .get(&user)
+                .ok_or(ProgramError::UninitializedAccount)?;
+            if !profile.eligible() {
+                return Err(NetworkingError::NotEligible.into());
+            }
+            if profile.posts.get(&post_author).copied().unwrap_or(0) <= post_index {
+                return Err(NetworkingError::PostNotFound.into());
+            }
+            (
+                ProfessionalNetworkingInstruction::AddComment {
+                    post_author,
+                    post_index,
+                    content,
+                },
+                vec![AccountMeta::new(user, true)],
+            )
+        }
+    };
+
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &data.try_to_vec()?,
+        accounts,
+    ))
+}
+
+// Разбирает строку из format_error_log (с префиксом "Program log: " или без).
+// Возвращает код ошибки и тег инструкции; None, если строка не того формата или версии.
+pub fn parse_error_log(line: &str) -> Option<(u64, Option<u8>)> {
+    let line = line.strip_prefix("Program log: ").unwrap_or(line);
+    let rest = line.strip_prefix(&format!("ERR v{} ", LOG_SCHEMA_VERSION))?;
+    let (fields, _reason) = rest.split_once(" reason=")?;
+    let (code, instruction_tag) = fields.split_once(' ')?;
+    let code = code.strip_prefix("code=")?.parse().ok()?;
+    let instruction_tag = match instruction_tag.strip_prefix("ix=")? {
+        "?" => None,
+        tag => Some(tag.parse().ok()?),
+    };
+    Some((code, instruction_tag))
+}
@@ -1,262 +1,3138 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use num_traits::{FromPrimitive, One, SaturatingAdd};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    decode_error::DecodeError,
     entrypoint,
     entrypoint::ProgramResult,
-    program::{invoke, invoke_signed},
-    program_error::ProgramError,
+    hash::hashv,
+    msg,
+    program::invoke_signed,
+    program_error::{PrintProgramError, ProgramError},
+    program_pack::Pack,
     pubkey::Pubkey,
     sysvar::{rent::Rent, Sysvar},
 };
 use std::{
+    cell::RefMut,
     collections::{HashMap, HashSet},
-    convert::TryInto,
+    fmt,
 };
 
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(test)]
+#[rustfmt::skip]
+mod test;
+
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const LOG_SCHEMA_VERSION: u8 = 1;
+pub const SNAPSHOT_SEED: &[u8] = b"snapshot";
+pub const INDEXER_SEED: &[u8] = b"indexer";
+pub const ANONYMOUS_BOARD_SEED: &[u8] = b"anonymous_board";
+pub const MAX_ORACLES: usize = 10;
+pub const MAX_EXTERNAL_IDENTITIES: usize = 5;
+pub const NFT_FRIEND_THRESHOLD: usize = 5;
+pub const MAX_POST_TAGS: usize = 5;
+pub const MAX_POST_ATTACHMENTS: usize = 4;
+pub const MAX_ATTACHMENT_URI_LEN: usize = 200;
+pub const MIN_TAG_LEN: usize = 2;
+pub const MAX_TAG_LEN: usize = 32;
+pub const MAX_DOMAIN_LEN: usize = 253;
+pub const MAX_COMMENTS_PER_POST: usize = 50;
+pub const IMPRESSION_DEDUP_WINDOW: usize = 32;
+pub const USER_REPORT_THRESHOLD: usize = 5;
+pub const MEMBERSHIP_DURATION_SECS: i64 = 365 * 24 * 60 * 60;
+pub const RELEVANCE_DECAY_SECS: f64 = 24.0 * 60.0 * 60.0;
+pub const MAX_PROFILE_SECTIONS: usize = 6;
+pub const MAX_SECTION_TITLE_LEN: usize = 48;
+pub const MAX_SECTION_BODY_LEN: usize = 1000;
+pub const MAX_PROFILE_ACCOUNT_LEN: usize = 64 * 1024;
+// Предел сериализованного профиля; дальше — только политика хранения постов
+pub const MAX_PROFILE_SIZE: usize = 32 * 1024;
+pub const MAX_CONTACT_BLOB_LEN: usize = 512;
+pub const MIN_TIMEZONE_OFFSET: i8 = -12;
+pub const MAX_TIMEZONE_OFFSET: i8 = 14;
+pub const DEFAULT_MAX_RETAINED_POSTS: u16 = 200;
+pub const MAX_RETAINED_POSTS_CEILING: u16 = 1000;
+pub const MAX_BOARD_POSTS: usize = 8;
+pub const MAX_BOARD_COMMENTS_PER_POST: usize = 3;
+pub const MAX_ANONYMOUS_TEXT_LEN: usize = 240;
+
+// Веса для UserProfile::reputation
+pub const REPUTATION_FRIEND_WEIGHT: u32 = 10;
+pub const REPUTATION_NFT_BONUS: u32 = 50;
+pub const REPUTATION_POST_WEIGHT: u32 = 5;
+pub const REPUTATION_COMMENT_WEIGHT: u32 = 2;
+pub const REPUTATION_IMPRESSIONS_PER_POINT: u32 = 10;
+
+// Enum ошибок, коды и сообщения генерируются из одной таблицы, чтобы не расходились.
+// Коды стабильны: новые варианты добавляются только в конец.
+macro_rules! networking_errors {
+    ($($variant:ident = $code:literal => $message:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum NetworkingError {
+            $($variant = $code,)*
+        }
+
+        pub const ERROR_TABLE: &[(u32, &str)] = &[$(($code, $message),)*];
+
+        impl NetworkingError {
+            pub fn from_code(code: u32) -> Option<Self> {
+                match code {
+                    $($code => Some(NetworkingError::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn code(&self) -> u32 {
+                *self as u32
+            }
+
+            pub fn message(&self) -> &'static str {
+                match self {
+                    $(NetworkingError::$variant => $message,)*
+                }
+            }
+        }
+    };
+}
+
+networking_errors! {
+    AlreadyFriends = 0 => "Users are already friends",
+    NotEligible = 1 => "The NFT and enough friends are required for this action",
+    PostNotFound = 2 => "Post not found",
+    Unauthorized = 3 => "Signer is not allowed to perform this action",
+    TooManyOracles = 4 => "Too many oracles in the config",
+    IdentityLimitReached = 5 => "External identity limit reached",
+    IdentityNotFound = 6 => "No external identity for this platform",
+    SelfReferral = 7 => "A user cannot refer themselves",
+    ReferrerMismatch = 8 => "Account is not the referrer of this profile",
+    ReferralAlreadyClaimed = 9 => "Referral has already been claimed",
+    ReferralNotEligible = 10 => "Referred user has not reached the NFT threshold yet",
+    TooManyTags = 11 => "Too many tags on a post",
+    CommentLimitReached = 12 => "Post has reached its comment limit",
+    ClockUnavailable = 13 => "Clock sysvar is unavailable",
+    InvalidTheme = 14 => "Unknown profile theme code",
+    CommentNotFound = 15 => "Comment not found",
+    ProfileDeactivated = 16 => "Profile is deactivated",
+    SelfReport = 17 => "A user cannot report themselves",
+    SectionLimitReached = 18 => "Profile section limit reached",
+    SectionNotFound = 19 => "Profile section not found",
+    SectionTooLong = 20 => "Profile section title or body is too long",
+    InvalidSectionOrder = 21 => "New section order is not a permutation of existing sections",
+    InvalidTag = 22 => "Tags must be 2-32 ASCII letters, digits or underscores",
+    InvalidDomain = 23 => "Invalid email domain",
+    ProfileNotInitialized = 24 => "Profile account is not initialized",
+    AccountTooLarge = 25 => "Profile account data exceeds the size limit",
+    InvalidRetentionPolicy = 26 => "Retained post limit is out of range",
+    RepairsDisabled = 27 => "Account repairs are disabled in the config",
+    TooManyAttachments = 28 => "Too many attachments on a post",
+    InvalidAttachmentUri = 29 => "Attachment URI must be https://, ipfs:// or ar:// without spaces",
+    AttachmentsImmutable = 30 => "Post attachments cannot be changed after publishing",
+    ContactBlobTooLarge = 31 => "Encrypted contact blob is too large",
+    NotFriends = 32 => "Users are not friends",
+    InvalidTimezone = 33 => "Time zone offset must be between -12 and +14 hours",
+    DuplicateAccount = 34 => "The same account was passed twice for a writable role",
+    BorrowConflict = 35 => "Account data is already borrowed",
+    AccountDataTooSmall = 36 => "Profile account is too small for this change",
+    ProfileSizeLimitReached = 37 => "Profile reached the maximum size; lower the post retention limit",
+    InvalidAvailability = 38 => "Availability hours must be between 0 and 23",
+    DuplicateAttachmentUri = 39 => "A post cannot attach the same URI twice",
+    ProfileAddressMismatch = 40 => "Profile is stored under a different account than its owner",
+    CheckpointRegression = 41 => "Indexer checkpoint cannot move backwards",
+    RevealMismatch = 42 => "Signer and salt do not match the anonymous post commitment",
+    AlreadyRevealed = 43 => "Anonymous post author is already revealed",
+    AnonymousTextTooLong = 44 => "Anonymous board post or comment is too long",
+}
+
+impl From<NetworkingError> for ProgramError {
+    fn from(e: NetworkingError) -> Self {
+        ProgramError::Custom(e.code())
+    }
+}
+
+impl fmt::Display for NetworkingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for NetworkingError {}
+
+impl FromPrimitive for NetworkingError {
+    fn from_i64(n: i64) -> Option<Self> {
+        u32::try_from(n).ok().and_then(Self::from_code)
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        u32::try_from(n).ok().and_then(Self::from_code)
+    }
+}
+
+impl<T> DecodeError<T> for NetworkingError {
+    fn type_of() -> &'static str {
+        "NetworkingError"
+    }
+}
+
+impl PrintProgramError for NetworkingError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!(self.message());
+    }
+}
+
 // Структура для хранения комментариев
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Comment {
     pub author: Pubkey,
     pub content: String,
+    pub quote_count: Counter<u32>,
 }
 
 impl Comment {
     pub fn new(author: Pubkey, content: String) -> Self {
-        Comment { author, content }
+        Comment {
+            author,
+            content,
+            quote_count: Counter::default(),
+        }
     }
 }
 
+// Вид поста: обычный, цитата комментария или анонимный вопрос доски. Цитата хранит
+// только хеш процитированного текста, оригинал клиенты берут из исходного поста.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum PostKind {
+    Original,
+    Quote {
+        source_post_author: Pubkey,
+        source_post_index: usize,
+        source_comment_index: usize,
+        quoted_hash: [u8; 32],
+    },
+    // commitment = sha256(author || salt), считается на клиенте (см. anonymous_commitment)
+    Anonymous {
+        commitment: [u8; 32],
+    },
+}
+
+// Вложение поста (презентация, PDF и т.д.): сам файл хранится вне сети,
+// content_hash позволяет проверить, что он не подменён
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub uri: String,
+    pub content_hash: [u8; 32],
+    pub mime_hint: u8,
+}
+
 // Структура для хранения постов
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Post {
     pub author: Pubkey,
     pub content: String,
     pub comments: Vec<Comment>,
+    pub tags: Vec<String>,
+    pub attachments: Vec<Attachment>,
+    pub impressions: Counter<u32>,
+    pub recent_viewers: Vec<u64>,
+    pub kind: PostKind,
+    pub timestamp: i64,
+    pub evicted: bool,
+}
+
+impl Post {
+    pub fn new(author: Pubkey, content: String, tags: Vec<String>, timestamp: i64) -> Self {
+        Post {
+            author,
+            content,
+            comments: Vec::new(),
+            tags,
+            attachments: Vec::new(),
+            impressions: Counter::default(),
+            recent_viewers: Vec::new(),
+            kind: PostKind::Original,
+            timestamp,
+            evicted: false,
+        }
+    }
+
+    // Надгробие вытесненного поста: текст удаляется, структура (индексы
+    // комментариев и цитаты на них) сохраняется
+    pub fn evict(&mut self) {
+        self.content.clear();
+        self.tags.clear();
+        self.attachments.clear();
+        self.recent_viewers.clear();
+        for comment in &mut self.comments {
+            comment.content.clear();
+        }
+        self.evicted = true;
+    }
+
+    pub fn add_comment(&mut self, author: Pubkey, content: String) -> ProgramResult {
+        if self.evicted {
+            return Err(NetworkingError::PostNotFound.into());
+        }
+        if self.comments.len() >= MAX_COMMENTS_PER_POST {
+            return Err(NetworkingError::CommentLimitReached.into());
+        }
+        self.comments.push(Comment::new(author, content));
+        Ok(())
+    }
+
+    // Счётчик показов приблизительный: повторы отсекаются только по последним
+    // IMPRESSION_DEDUP_WINDOW зрителям, хранятся 64-битные отпечатки, а не ключи,
+    // поэтому зритель, вытесненный из окна, будет засчитан снова, а редкая коллизия
+    // отпечатков может не засчитать нового. Показы автора не считаются.
+    // Возвращает true, если показ засчитан.
+    pub fn record_impression(
+        &mut self,
+        viewer: &Pubkey,
+        post_index: usize,
+    ) -> Result<bool, ProgramError> {
+        if self.evicted {
+            return Err(NetworkingError::PostNotFound.into());
+        }
+        if *viewer == self.author {
+            return Ok(false);
+        }
+        let hash = hashv(&[
+            viewer.as_ref(),
+            self.author.as_ref(),
+            &(post_index as u64).to_le_bytes(),
+        ]);
+        let fingerprint = u64::from_le_bytes(hash.to_bytes()[..8].try_into().unwrap());
+        if self.recent_viewers.contains(&fingerprint) {
+            return Ok(false);
+        }
+        self.impressions.increment();
+        if self.recent_viewers.len() >= IMPRESSION_DEDUP_WINDOW {
+            self.recent_viewers.remove(0);
+        }
+        self.recent_viewers.push(fingerprint);
+        Ok(true)
+    }
+
+    // Вовлечённость (1 + комментарии + показы / REPUTATION_IMPRESSIONS_PER_POINT),
+    // затухающая экспоненциально с возрастом поста: за RELEVANCE_DECAY_SECS
+    // вес падает в e раз
+    pub fn relevance(&self, now: i64) -> f64 {
+        let engagement = 1.0
+            + self.comments.len() as f64
+            + self.impressions.get() as f64 / REPUTATION_IMPRESSIONS_PER_POINT as f64;
+        let age = now.saturating_sub(self.timestamp).max(0) as f64;
+        engagement * (-age / RELEVANCE_DECAY_SECS).exp()
+    }
+
+    // У каждого комментария должен быть реальный автор, а не нулевой ключ.
+    // Упоминаний в комментариях нет, так что проверять больше нечего.
+    pub fn authors_consistent(&self) -> bool {
+        self.comments
+            .iter()
+            .all(|comment| comment.author != Pubkey::default())
+    }
+
+    // Доля заполненности секции комментариев, от 0.0 до 1.0
+    pub fn comment_capacity_used(&self) -> f64 {
+        (self.comments.len() as f64 / MAX_COMMENTS_PER_POST as f64).min(1.0)
+    }
+}
+
+// Подтверждённая оракулом внешняя учётная запись (Twitter, GitHub и т.д.).
+// Хранится только солёный хеш хэндла, чтобы не раскрывать его в сети.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ExternalIdentity {
+    pub platform: u8,
+    pub handle_hash: [u8; 32],
+    pub attested_by: Pubkey,
+    pub attested_at: i64,
+}
+
+// Произвольный раздел профиля ("Публикации", "Выступления" и т.д.)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ProfileSection {
+    pub title: String,
+    pub body: String,
+    pub order: u8,
+}
+
+impl ProfileSection {
+    pub fn new(title: String, body: String, order: u8) -> Result<Self, ProgramError> {
+        if title.len() > MAX_SECTION_TITLE_LEN || body.len() > MAX_SECTION_BODY_LEN {
+            return Err(NetworkingError::SectionTooLong.into());
+        }
+        Ok(ProfileSection { title, body, order })
+    }
+}
+
+// Минт и токен-аккаунт выданного NFT участника, чтобы сверять nft_owned с сетью
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MembershipNft {
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+}
+
+// Изменения, увеличивающие профиль, для предварительной оценки роста
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MutationKind {
+    AddFriend,
+    RecordMembershipNft,
+    AddPost {
+        content_len: usize,
+        tags: usize,
+        attachments: usize,
+    },
+    AddQuotePost {
+        commentary_len: usize,
+    },
+    AddComment {
+        content_len: usize,
+    },
+    ShareContact {
+        blob_len: usize,
+    },
+}
+
+// Цветовые темы профиля; в аккаунте хранится код темы
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Default = 0,
+    Light = 1,
+    Dark = 2,
+    Ocean = 3,
+    Forest = 4,
+}
+
+impl Theme {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Theme::Default),
+            1 => Some(Theme::Light),
+            2 => Some(Theme::Dark),
+            3 => Some(Theme::Ocean),
+            4 => Some(Theme::Forest),
+            _ => None,
+        }
+    }
+}
+
+// Проблема, найденная UserProfile::integrity_check
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityIssue {
+    // Для ошибок декодирования — поле и смещение его начала в данных аккаунта
+    Truncated {
+        field: &'static str,
+        offset: usize,
+    },
+    InvalidUtf8 {
+        field: &'static str,
+        offset: usize,
+    },
+    Undecodable {
+        field: &'static str,
+        offset: usize,
+    },
+    NotInitialized,
+    SelfInFriends,
+    OverCap {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+}
+
+// Курсор для UserProfile::walk_fields. Причина сбоя определяется по исходу,
+// а не по тексту ошибки: если поле декодируется после добивки нулями — данные
+// обрезаны; если декодируется его вариант с байтами вместо строк — битый UTF-8
+struct FieldCursor<'a> {
+    data: &'a [u8],
+    rest: &'a [u8],
+}
+
+impl<'a> FieldCursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        FieldCursor { data, rest: data }
+    }
+
+    fn offset(&self) -> usize {
+        self.data.len() - self.rest.len()
+    }
+
+    fn read<T: BorshDeserialize>(&mut self, field: &'static str) -> Result<(), IntegrityIssue> {
+        self.read_as::<T, T>(field)
+    }
+
+    // Raw — тип с той же раскладкой, что и T, но с Vec<u8> на месте строк
+    fn read_as<T: BorshDeserialize, Raw: BorshDeserialize>(
+        &mut self,
+        field: &'static str,
+    ) -> Result<(), IntegrityIssue> {
+        let offset = self.offset();
+        let mut rest = self.rest;
+        if T::deserialize(&mut rest).is_ok() {
+            self.rest = rest;
+            return Ok(());
+        }
+        let mut padded = self.rest.to_vec();
+        padded.resize(self.rest.len() + MAX_PROFILE_ACCOUNT_LEN, 0);
+        Err(if T::deserialize(&mut &padded[..]).is_ok() {
+            IntegrityIssue::Truncated { field, offset }
+        } else if Raw::deserialize(&mut &padded[..]).is_ok() {
+            IntegrityIssue::InvalidUtf8 { field, offset }
+        } else {
+            IntegrityIssue::Undecodable { field, offset }
+        })
+    }
+}
+
+// Отчёт для мониторинга: пустой список — аккаунт в порядке
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn check_cap(&mut self, field: &'static str, len: usize, max: usize) {
+        if len > max {
+            self.issues
+                .push(IntegrityIssue::OverCap { field, len, max });
+        }
+    }
+}
+
+// Структура для профиля пользователя
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct UserProfile {
+    pub is_initialized: bool,
+    pub name: String,
+    pub bio: String,
+    pub profile_picture: String,
+    pub address: Pubkey,
+    pub friends: HashSet<Pubkey>,
+    pub nft_owned: bool,
+    pub posts: HashMap<Pubkey, Vec<Post>>,
+    pub external_identities: Vec<ExternalIdentity>,
+    pub referred_by: Option<Pubkey>,
+    pub referral_claimed: bool,
+    pub referral_count: Counter<u32>,
+    pub theme: u8,
+    // Хранится флаг деактивации, а не активности: у аккаунтов, записанных до
+    // появления поля, на его месте нулевой хвост, и они должны читаться активными
+    pub deactivated: bool,
+    pub user_reports: Vec<(Pubkey, u8)>,
+    pub flagged: bool,
+    pub membership_expires_at: i64,
+    pub sections: Vec<ProfileSection>,
+    pub verified_domain: Option<String>,
+    pub max_retained_posts: u16,
+    pub membership_nft: Option<MembershipNft>,
+    // Контакты шифруются на клиенте (X25519); программа хранит их как непрозрачные байты
+    pub encryption_pubkey: Option<[u8; 32]>,
+    pub private_contact: Vec<u8>,
+    pub shared_contacts: Vec<(Pubkey, Vec<u8>)>,
+    pub identicon_seed: [u8; 8],
+    pub timezone_offset: i8,
+    pub requests_sent: Counter<u32>,
+    pub requests_accepted: Counter<u32>,
+    pub available_hours: Option<(u8, u8)>,
 }
 
-impl Post {
-    pub fn new(author: Pubkey, content: String) -> Self {
-        Post {
-            author,
-            content,
-            comments: Vec::new(),
+impl UserProfile {
+    pub fn new(name: String, bio: String, profile_picture: String, address: Pubkey) -> Self {
+        UserProfile {
+            is_initialized: true,
+            name,
+            bio,
+            profile_picture,
+            address,
+            friends: HashSet::new(),
+            nft_owned: false,
+            posts: HashMap::new(),
+            external_identities: Vec::new(),
+            referred_by: None,
+            referral_claimed: false,
+            referral_count: Counter::default(),
+            theme: Theme::Default as u8,
+            deactivated: false,
+            user_reports: Vec::new(),
+            flagged: false,
+            membership_expires_at: 0,
+            sections: Vec::new(),
+            verified_domain: None,
+            max_retained_posts: DEFAULT_MAX_RETAINED_POSTS,
+            membership_nft: None,
+            encryption_pubkey: None,
+            private_contact: Vec::new(),
+            shared_contacts: Vec::new(),
+            identicon_seed: identicon_seed(&address),
+            timezone_offset: 0,
+            requests_sent: Counter::default(),
+            requests_accepted: Counter::default(),
+            available_hours: None,
+        }
+    }
+
+    // Общая загрузка профиля из данных аккаунта. Пустой или неинициализированный
+    // аккаунт и аккаунт больше MAX_PROFILE_ACCOUNT_LEN отсекаются до декодирования;
+    // хвост аккаунта после профиля (нули) допустим.
+    pub fn load(data: &[u8]) -> Result<UserProfile, ProgramError> {
+        if data.first().copied().unwrap_or(0) == 0 {
+            msg!(
+                "Profile account is empty or not initialized ({} bytes)",
+                data.len()
+            );
+            return Err(NetworkingError::ProfileNotInitialized.into());
+        }
+        if data.len() > MAX_PROFILE_ACCOUNT_LEN {
+            msg!(
+                "Profile account is {} bytes, limit is {}",
+                data.len(),
+                MAX_PROFILE_ACCOUNT_LEN
+            );
+            return Err(NetworkingError::AccountTooLarge.into());
+        }
+        UserProfile::deserialize(&mut &data[..]).map_err(|e| {
+            msg!("Failed to decode profile: {}", e);
+            ProgramError::InvalidAccountData
+        })
+    }
+
+    // Проверяет сырые данные аккаунта без паники: сначала декодирование,
+    // затем инварианты, которые программа поддерживает при записи
+    pub fn integrity_check(data: &[u8]) -> IntegrityReport {
+        let mut report = IntegrityReport::default();
+        let profile = match UserProfile::deserialize(&mut &data[..]) {
+            Ok(profile) => profile,
+            Err(_) => {
+                // Текст ошибки borsh не стабилен, поэтому место и причину сбоя
+                // находим повторным проходом по полям
+                let issue = match UserProfile::walk_fields(data) {
+                    Err(issue) => issue,
+                    Ok(offset) => IntegrityIssue::Undecodable {
+                        field: "profile",
+                        offset,
+                    },
+                };
+                report.issues.push(issue);
+                return report;
+            }
+        };
+
+        if !profile.is_initialized {
+            report.issues.push(IntegrityIssue::NotInitialized);
+        }
+        if profile.friends.contains(&profile.address) {
+            report.issues.push(IntegrityIssue::SelfInFriends);
+        }
+        report.check_cap(
+            "external_identities",
+            profile.external_identities.len(),
+            MAX_EXTERNAL_IDENTITIES,
+        );
+        report.check_cap("sections", profile.sections.len(), MAX_PROFILE_SECTIONS);
+        report.check_cap(
+            "private_contact",
+            profile.private_contact.len(),
+            MAX_CONTACT_BLOB_LEN,
+        );
+        for (_, blob) in &profile.shared_contacts {
+            report.check_cap("shared_contacts.blob", blob.len(), MAX_CONTACT_BLOB_LEN);
+        }
+        for post in profile.posts.values().flatten() {
+            report.check_cap("post.tags", post.tags.len(), MAX_POST_TAGS);
+            report.check_cap("post.comments", post.comments.len(), MAX_COMMENTS_PER_POST);
+            report.check_cap(
+                "post.recent_viewers",
+                post.recent_viewers.len(),
+                IMPRESSION_DEDUP_WINDOW,
+            );
+            report.check_cap(
+                "post.attachments",
+                post.attachments.len(),
+                MAX_POST_ATTACHMENTS,
+            );
+        }
+        report
+    }
+
+    // Проходит поля профиля в порядке сериализации и возвращает длину профиля
+    // или первую проблему декодирования. Порядок полей должен совпадать со структурой
+    fn walk_fields(data: &[u8]) -> Result<usize, IntegrityIssue> {
+        let mut cursor = FieldCursor::new(data);
+        cursor.read::<bool>("is_initialized")?;
+        cursor.read_as::<String, Vec<u8>>("name")?;
+        cursor.read_as::<String, Vec<u8>>("bio")?;
+        cursor.read_as::<String, Vec<u8>>("profile_picture")?;
+        cursor.read::<Pubkey>("address")?;
+        cursor.read::<HashSet<Pubkey>>("friends")?;
+        cursor.read::<bool>("nft_owned")?;
+        cursor.read::<HashMap<Pubkey, Vec<Post>>>("posts")?;
+        cursor.read::<Vec<ExternalIdentity>>("external_identities")?;
+        cursor.read::<Option<Pubkey>>("referred_by")?;
+        cursor.read::<bool>("referral_claimed")?;
+        cursor.read::<Counter<u32>>("referral_count")?;
+        cursor.read::<u8>("theme")?;
+        cursor.read::<bool>("deactivated")?;
+        cursor.read::<Vec<(Pubkey, u8)>>("user_reports")?;
+        cursor.read::<bool>("flagged")?;
+        cursor.read::<i64>("membership_expires_at")?;
+        cursor.read_as::<Vec<ProfileSection>, Vec<(Vec<u8>, Vec<u8>, u8)>>("sections")?;
+        cursor.read_as::<Option<String>, Option<Vec<u8>>>("verified_domain")?;
+        cursor.read::<u16>("max_retained_posts")?;
+        cursor.read::<Option<MembershipNft>>("membership_nft")?;
+        cursor.read::<Option<[u8; 32]>>("encryption_pubkey")?;
+        cursor.read::<Vec<u8>>("private_contact")?;
+        cursor.read::<Vec<(Pubkey, Vec<u8>)>>("shared_contacts")?;
+        cursor.read::<[u8; 8]>("identicon_seed")?;
+        cursor.read::<i8>("timezone_offset")?;
+        cursor.read::<Counter<u32>>("requests_sent")?;
+        cursor.read::<Counter<u32>>("requests_accepted")?;
+        cursor.read::<Option<(u8, u8)>>("available_hours")?;
+        Ok(cursor.offset())
+    }
+
+    // Деактивированный профиль доступен для чтения, но не может постить, комментировать и дружить
+    pub fn is_active(&self) -> bool {
+        !self.deactivated
+    }
+
+    pub fn ensure_active(&self) -> ProgramResult {
+        if !self.is_active() {
+            return Err(NetworkingError::ProfileDeactivated.into());
+        }
+        Ok(())
+    }
+
+    // Членство по NFT действует год; после истечения токен остаётся,
+    // но права на посты и комментарии пропадают до RenewMembership
+    pub fn membership_active(&self, now: i64) -> bool {
+        if !self.nft_owned {
+            return false;
+        }
+        if now >= self.membership_expires_at {
+            msg!(
+                "Membership expired at {}, renew to keep posting",
+                self.membership_expires_at
+            );
+            return false;
+        }
+        true
+    }
+
+    pub fn grant_membership(&mut self, now: i64) {
+        self.nft_owned = true;
+        self.membership_expires_at = now.saturating_add(MEMBERSHIP_DURATION_SECS);
+    }
+
+    // Приводит nft_owned к тому, держит ли профиль NFT на самом деле.
+    // При отзыве членство тоже сбрасывается. Возвращает true, если что-то изменилось.
+    pub fn reconcile_membership(&mut self, holds_nft: bool) -> bool {
+        if holds_nft == self.nft_owned {
+            return false;
+        }
+        self.nft_owned = holds_nft;
+        if !holds_nft {
+            self.membership_expires_at = 0;
+        }
+        true
+    }
+
+    // confirmed_friends — друзья, подтвердившие дружбу со своей стороны (см. is_confirmed_friend):
+    // односторонние записи SendFriendRequest продлению не помогают
+    pub fn renew_membership(
+        &mut self,
+        now: i64,
+        confirmed_friends: usize,
+        threshold: usize,
+    ) -> ProgramResult {
+        if !self.nft_owned || confirmed_friends < threshold {
+            msg!(
+                "Renewal needs {} confirmed friends, have {}",
+                threshold,
+                confirmed_friends
+            );
+            return Err(NetworkingError::NotEligible.into());
+        }
+        self.grant_membership(now);
+        Ok(())
+    }
+
+    pub fn is_confirmed_friend(&self, friend: &UserProfile) -> bool {
+        self.friends.contains(&friend.address) && friend.friends.contains(&self.address)
+    }
+
+    // Доведёт ли дружба с friend_address до порога NFT, которого ещё нет
+    pub fn reaches_nft_threshold_with(&self, friend_address: &Pubkey) -> bool {
+        let new_friend = usize::from(!self.friends.contains(friend_address));
+        !self.nft_owned && self.friends.len() + new_friend >= NFT_FRIEND_THRESHOLD
+    }
+
+    pub fn send_friend_request(&mut self, friend_address: Pubkey) -> ProgramResult {
+        if !self.friends.insert(friend_address) {
+            return Err(NetworkingError::AlreadyFriends.into());
+        }
+        self.requests_sent.increment();
+        Ok(())
+    }
+
+    // Дружба в обе стороны; false, если профили уже друзья с обеих сторон.
+    // Если заявку раньше отправил friend, засчитывает ему принятие.
+    pub fn link_friends(&mut self, friend: &mut UserProfile) -> Result<bool, ProgramError> {
+        let added = self.friends.insert(friend.address);
+        let added_back = friend.friends.insert(self.address);
+        if added && !added_back {
+            friend.requests_accepted.increment();
+        }
+        Ok(added || added_back)
+    }
+
+    // Доля принятых заявок среди отправленных; низкая — признак спама
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.requests_sent.get() == 0 {
+            return 0.0;
+        }
+        (self.requests_accepted.get() as f64 / self.requests_sent.get() as f64).min(1.0)
+    }
+
+    pub fn can_write_post(&self, now: i64) -> bool {
+        self.membership_active(now) && self.friends.len() >= NFT_FRIEND_THRESHOLD
+    }
+
+    pub fn can_comment(&self, now: i64) -> bool {
+        self.membership_active(now) && self.friends.len() >= NFT_FRIEND_THRESHOLD
+    }
+
+    // Возвращает индексы постов, вытесненных политикой хранения
+    pub fn add_post(
+        &mut self,
+        author: Pubkey,
+        content: String,
+        tags: Vec<String>,
+        attachments: Vec<Attachment>,
+        timestamp: i64,
+    ) -> Vec<usize> {
+        let mut post = Post::new(author, content, tags, timestamp);
+        // Вложения задаются только при создании поста
+        post.attachments = attachments;
+        self.push_post(post)
+    }
+
+    fn push_post(&mut self, post: Post) -> Vec<usize> {
+        let author = post.author;
+        let limit = self.retention_limit();
+        let posts = self.posts.entry(author).or_default();
+        posts.push(post);
+
+        // Сверх лимита вытесняются самые старые живые посты. Событие PostEvicted —
+        // страховка для индексаторов, клиентам лучше архивировать заранее.
+        let live = posts.iter().filter(|post| !post.evicted).count();
+        let excess = live.saturating_sub(limit);
+        let mut evicted = Vec::new();
+        for (index, post) in posts.iter_mut().enumerate() {
+            if evicted.len() == excess {
+                break;
+            }
+            if !post.evicted {
+                post.evict();
+                msg!("PostEvicted: author={} index={}", author, index);
+                evicted.push(index);
+            }
+        }
+        evicted
+    }
+
+    // Профили, созданные до появления поля, хранят 0 — для них действует DEFAULT_MAX_RETAINED_POSTS
+    pub fn retention_limit(&self) -> usize {
+        match self.max_retained_posts {
+            0 => DEFAULT_MAX_RETAINED_POSTS as usize,
+            limit => limit as usize,
+        }
+    }
+
+    // Сеттеры настроек возвращают false, если значение не изменилось
+    pub fn set_retention_policy(&mut self, max_retained_posts: u16) -> Result<bool, ProgramError> {
+        if max_retained_posts == 0 || max_retained_posts > MAX_RETAINED_POSTS_CEILING {
+            return Err(NetworkingError::InvalidRetentionPolicy.into());
+        }
+        Ok(set_if_changed(
+            &mut self.max_retained_posts,
+            max_retained_posts,
+        ))
+    }
+
+    pub fn posts_with_tag(&self, author: &Pubkey, tag: &str) -> Vec<&Post> {
+        let Ok(tag) = normalize_tag(tag) else {
+            return Vec::new();
+        };
+        self.posts
+            .get(author)
+            .map(|posts| {
+                posts
+                    .iter()
+                    .filter(|post| post.tags.contains(&tag))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn add_comment(
+        &mut self,
+        post_author: Pubkey,
+        post_index: usize,
+        comment_author: Pubkey,
+        content: String,
+    ) -> ProgramResult {
+        if let Some(posts) = self.posts.get_mut(&post_author) {
+            if let Some(post) = posts.get_mut(post_index) {
+                return post.add_comment(comment_author, content);
+            }
+        }
+        Err(NetworkingError::PostNotFound.into())
+    }
+
+    pub fn get_post_with_comments(&self, author: &Pubkey, post_index: usize) -> Option<&Post> {
+        self.posts.get(author)?.get(post_index)
+    }
+
+    // Отмечает цитирование комментария и возвращает вид поста-цитаты
+    pub fn quote_comment(
+        &mut self,
+        post_author: Pubkey,
+        post_index: usize,
+        comment_index: usize,
+    ) -> Result<PostKind, ProgramError> {
+        let post = self
+            .posts
+            .get_mut(&post_author)
+            .and_then(|posts| posts.get_mut(post_index))
+            .filter(|post| !post.evicted)
+            .ok_or(NetworkingError::PostNotFound)?;
+        let comment = post
+            .comments
+            .get_mut(comment_index)
+            .ok_or(NetworkingError::CommentNotFound)?;
+
+        comment.quote_count.increment();
+        Ok(PostKind::Quote {
+            source_post_author: post_author,
+            source_post_index: post_index,
+            source_comment_index: comment_index,
+            quoted_hash: hashv(&[comment.content.as_bytes()]).to_bytes(),
+        })
+    }
+
+    pub fn add_quote_post(
+        &mut self,
+        author: Pubkey,
+        commentary: Option<String>,
+        kind: PostKind,
+        timestamp: i64,
+    ) -> Vec<usize> {
+        let mut post = Post::new(
+            author,
+            commentary.unwrap_or_default(),
+            Vec::new(),
+            timestamp,
+        );
+        post.kind = kind;
+        self.push_post(post)
+    }
+
+    // Репутация = друзья * REPUTATION_FRIEND_WEIGHT
+    //     + REPUTATION_NFT_BONUS, если есть NFT
+    //     + свои посты * REPUTATION_POST_WEIGHT
+    //     + комментарии к своим постам * REPUTATION_COMMENT_WEIGHT
+    //     + показы своих постов / REPUTATION_IMPRESSIONS_PER_POINT
+    pub fn reputation(&self) -> u32 {
+        let own_posts = self
+            .posts
+            .get(&self.address)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let comments: usize = own_posts.iter().map(|post| post.comments.len()).sum();
+        let impressions: u32 = own_posts.iter().fold(0u32, |total, post| {
+            total.saturating_add(post.impressions.get())
+        });
+
+        (self.friends.len() as u32)
+            .saturating_mul(REPUTATION_FRIEND_WEIGHT)
+            .saturating_add(if self.nft_owned {
+                REPUTATION_NFT_BONUS
+            } else {
+                0
+            })
+            .saturating_add((own_posts.len() as u32).saturating_mul(REPUTATION_POST_WEIGHT))
+            .saturating_add((comments as u32).saturating_mul(REPUTATION_COMMENT_WEIGHT))
+            .saturating_add(impressions / REPUTATION_IMPRESSIONS_PER_POINT)
+    }
+
+    // Верхняя оценка прироста сериализованного профиля (в байтах) от изменения
+    pub fn estimate_growth(&self, op: &MutationKind) -> usize {
+        let pubkey = 32;
+        let len_prefix = 4;
+        // Пост может оказаться первым в HashMap для автора (владелец профиля)
+        let posts_entry = if self.posts.contains_key(&self.address) {
+            0
+        } else {
+            pubkey + len_prefix
+        };
+        let empty_post = Post::new(self.address, String::new(), Vec::new(), 0)
+            .try_to_vec()
+            .map_or(0, |data| data.len());
+        match *op {
+            MutationKind::AddFriend => pubkey,
+            MutationKind::RecordMembershipNft => 2 * pubkey,
+            MutationKind::AddPost {
+                content_len,
+                tags,
+                attachments,
+            } => {
+                posts_entry
+                    + empty_post
+                    + content_len
+                    + tags * (len_prefix + MAX_TAG_LEN)
+                    + attachments * (len_prefix + MAX_ATTACHMENT_URI_LEN + 32 + 1)
+            }
+            MutationKind::AddQuotePost { commentary_len } => {
+                // Автор, два индекса и хеш цитаты
+                posts_entry + empty_post + commentary_len + pubkey + 8 + 8 + 32
+            }
+            MutationKind::AddComment { content_len } => pubkey + len_prefix + content_len + 4,
+            MutationKind::ShareContact { blob_len } => pubkey + len_prefix + blob_len,
+        }
+    }
+
+    // Проверка до изменения: профиль после op не должен выйти за MAX_PROFILE_SIZE
+    // и за размер аккаунта available
+    pub fn ensure_room(&self, op: &MutationKind, available: usize) -> ProgramResult {
+        let needed = self.try_to_vec()?.len() + self.estimate_growth(op);
+        if needed > MAX_PROFILE_SIZE {
+            msg!(
+                "ProfileSizeLimitReached: needed={} max={}",
+                needed,
+                MAX_PROFILE_SIZE
+            );
+            return Err(NetworkingError::ProfileSizeLimitReached.into());
+        }
+        if needed > available {
+            msg!(
+                "AccountDataTooSmall: needed={} available={}",
+                needed,
+                available
+            );
+            return Err(NetworkingError::AccountDataTooSmall.into());
+        }
+        Ok(())
+    }
+
+    // Оценка размера сериализованного профиля после ещё additional_posts собственных
+    // постов длиной avg_post_len, без тегов и комментариев — для выделения места заранее
+    pub fn projected_size_after(&self, additional_posts: usize, avg_post_len: usize) -> usize {
+        let current = self.try_to_vec().map_or(0, |data| data.len());
+        if additional_posts == 0 {
+            return current;
+        }
+        let new_entry = if self.posts.contains_key(&self.address) {
+            0
+        } else {
+            // Ключ автора и длина вектора постов в HashMap
+            32 + 4
+        };
+        let empty_post = Post::new(self.address, String::new(), Vec::new(), 0)
+            .try_to_vec()
+            .map_or(0, |data| data.len());
+        current + new_entry + additional_posts * (empty_post + avg_post_len)
+    }
+
+    // Окно [start, start + window_secs) с наибольшим числом постов автора; окна
+    // начинаются с моментов публикации, при равенстве берётся самое раннее
+    pub fn busiest_window(&self, author: &Pubkey, window_secs: i64) -> Option<(i64, usize)> {
+        let mut timestamps: Vec<i64> = self
+            .posts
+            .get(author)?
+            .iter()
+            .map(|post| post.timestamp)
+            .collect();
+        if timestamps.is_empty() || window_secs <= 0 {
+            return None;
+        }
+        timestamps.sort_unstable();
+
+        let mut best = (timestamps[0], 0);
+        let mut end = 0;
+        for (start, &from) in timestamps.iter().enumerate() {
+            let until = from.saturating_add(window_secs);
+            while end < timestamps.len() && timestamps[end] < until {
+                end += 1;
+            }
+            if end - start > best.1 {
+                best = (from, end - start);
+            }
+        }
+        Some(best)
+    }
+
+    // Время последней активности; время есть только у постов, у комментариев и заявок его нет
+    pub fn last_activity(&self) -> Option<i64> {
+        self.posts
+            .get(&self.address)?
+            .iter()
+            .map(|post| post.timestamp)
+            .max()
+    }
+
+    // Профиль без активности дольше max_idle_secs (или совсем без неё) считается заброшенным
+    pub fn is_stale(&self, now: i64, max_idle_secs: i64) -> bool {
+        match self.last_activity() {
+            Some(last) => now.saturating_sub(last) > max_idle_secs,
+            None => true,
+        }
+    }
+
+    // Страница друзей в порядке возрастания ключей: порядок HashSet не стабилен
+    pub fn friends_page(&self, offset: usize, limit: usize) -> Vec<Pubkey> {
+        let mut friends: Vec<Pubkey> = self.friends.iter().copied().collect();
+        friends.sort();
+        friends.into_iter().skip(offset).take(limit).collect()
+    }
+
+    // Все, с кем взаимодействует профиль: друзья, пригласивший и комментаторы постов
+    pub fn all_contacts(&self) -> HashSet<Pubkey> {
+        let mut contacts: HashSet<Pubkey> = self.friends.clone();
+        contacts.extend(self.referred_by);
+        contacts.extend(
+            self.posts
+                .values()
+                .flatten()
+                .flat_map(|post| post.comments.iter().map(|comment| comment.author)),
+        );
+        contacts.remove(&self.address);
+        contacts
+    }
+
+    // Одна жалоба на репортёра (повторная обновляет причину); после
+    // USER_REPORT_THRESHOLD жалоб профиль помечается для модераторов
+    pub fn report(&mut self, reporter: Pubkey, reason_code: u8) -> ProgramResult {
+        if reporter == self.address {
+            return Err(NetworkingError::SelfReport.into());
+        }
+        match self
+            .user_reports
+            .iter_mut()
+            .find(|(key, _)| *key == reporter)
+        {
+            Some(report) => report.1 = reason_code,
+            None => self.user_reports.push((reporter, reason_code)),
+        }
+        if self.user_reports.len() >= USER_REPORT_THRESHOLD {
+            self.flagged = true;
+        }
+        Ok(())
+    }
+
+    pub fn add_section(&mut self, title: String, body: String) -> ProgramResult {
+        if self.sections.len() >= MAX_PROFILE_SECTIONS {
+            return Err(NetworkingError::SectionLimitReached.into());
+        }
+        let section = ProfileSection::new(title, body, self.sections.len() as u8)?;
+        self.sections.push(section);
+        Ok(())
+    }
+
+    pub fn update_section(&mut self, index: u8, title: String, body: String) -> ProgramResult {
+        let section = self
+            .sections
+            .get_mut(index as usize)
+            .ok_or(NetworkingError::SectionNotFound)?;
+        *section = ProfileSection::new(title, body, section.order)?;
+        Ok(())
+    }
+
+    pub fn remove_section(&mut self, index: u8) -> ProgramResult {
+        if index as usize >= self.sections.len() {
+            return Err(NetworkingError::SectionNotFound.into());
+        }
+        self.sections.remove(index as usize);
+        self.renumber_sections();
+        Ok(())
+    }
+
+    // new_order[i] — текущий индекс раздела, который должен стать i-м
+    pub fn reorder_sections(&mut self, new_order: &[u8]) -> ProgramResult {
+        let mut seen = vec![false; self.sections.len()];
+        if new_order.len() != self.sections.len() {
+            return Err(NetworkingError::InvalidSectionOrder.into());
+        }
+        for index in new_order {
+            match seen.get_mut(*index as usize) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(NetworkingError::InvalidSectionOrder.into()),
+            }
+        }
+        self.sections = new_order
+            .iter()
+            .map(|index| self.sections[*index as usize].clone())
+            .collect();
+        self.renumber_sections();
+        Ok(())
+    }
+
+    fn renumber_sections(&mut self) {
+        for (order, section) in self.sections.iter_mut().enumerate() {
+            section.order = order as u8;
+        }
+    }
+
+    // Засчитывает реферал пригласившему, когда приглашённый достиг порога NFT
+    pub fn claim_referral(&mut self, referrer: &mut UserProfile) -> ProgramResult {
+        if referrer.address == self.address {
+            return Err(NetworkingError::SelfReferral.into());
+        }
+        if self.referred_by != Some(referrer.address) {
+            return Err(NetworkingError::ReferrerMismatch.into());
+        }
+        if self.referral_claimed {
+            return Err(NetworkingError::ReferralAlreadyClaimed.into());
+        }
+        if !self.nft_owned {
+            return Err(NetworkingError::ReferralNotEligible.into());
+        }
+        referrer.referral_count.increment();
+        self.referral_claimed = true;
+        Ok(())
+    }
+
+    pub fn set_theme(&mut self, theme: u8) -> Result<bool, ProgramError> {
+        Theme::from_code(theme).ok_or(NetworkingError::InvalidTheme)?;
+        Ok(set_if_changed(&mut self.theme, theme))
+    }
+
+    // Смещение от UTC в часах; используется клиентами для отображения времени постов
+    pub fn set_timezone(&mut self, offset: i8) -> Result<bool, ProgramError> {
+        if !(MIN_TIMEZONE_OFFSET..=MAX_TIMEZONE_OFFSET).contains(&offset) {
+            return Err(NetworkingError::InvalidTimezone.into());
+        }
+        Ok(set_if_changed(&mut self.timezone_offset, offset))
+    }
+
+    // Часы доступности в часовом поясе профиля; start > end — окно через полночь
+    pub fn set_availability(&mut self, start: u8, end: u8) -> Result<bool, ProgramError> {
+        if start > 23 || end > 23 {
+            return Err(NetworkingError::InvalidAvailability.into());
+        }
+        Ok(set_if_changed(
+            &mut self.available_hours,
+            Some((start, end)),
+        ))
+    }
+
+    pub fn set_private_contact(&mut self, blob: Vec<u8>) -> Result<bool, ProgramError> {
+        if blob.len() > MAX_CONTACT_BLOB_LEN {
+            return Err(NetworkingError::ContactBlobTooLarge.into());
+        }
+        Ok(set_if_changed(&mut self.private_contact, blob))
+    }
+
+    // Контакт, перешифрованный отправителем для этого профиля. Только между
+    // взаимными друзьями; новый блоб от того же отправителя заменяет старый.
+    pub fn receive_shared_contact(&mut self, from: &UserProfile, blob: Vec<u8>) -> ProgramResult {
+        if blob.len() > MAX_CONTACT_BLOB_LEN {
+            return Err(NetworkingError::ContactBlobTooLarge.into());
+        }
+        if !self.friends.contains(&from.address) || !from.friends.contains(&self.address) {
+            return Err(NetworkingError::NotFriends.into());
+        }
+        match self
+            .shared_contacts
+            .iter_mut()
+            .find(|(sender, _)| *sender == from.address)
+        {
+            Some(entry) => entry.1 = blob,
+            None => self.shared_contacts.push((from.address, blob)),
+        }
+        Ok(())
+    }
+
+    // Одна запись на платформу: повторная аттестация заменяет старую
+    pub fn attest_external_identity(&mut self, identity: ExternalIdentity) -> ProgramResult {
+        if let Some(existing) = self
+            .external_identities
+            .iter_mut()
+            .find(|i| i.platform == identity.platform)
+        {
+            *existing = identity;
+            return Ok(());
+        }
+        if self.external_identities.len() >= MAX_EXTERNAL_IDENTITIES {
+            return Err(NetworkingError::IdentityLimitReached.into());
+        }
+        self.external_identities.push(identity);
+        Ok(())
+    }
+
+    pub fn remove_external_identity(&mut self, platform: u8) -> ProgramResult {
+        let len_before = self.external_identities.len();
+        self.external_identities.retain(|i| i.platform != platform);
+        if self.external_identities.len() == len_before {
+            return Err(NetworkingError::IdentityNotFound.into());
+        }
+        Ok(())
+    }
+}
+
+pub fn log_schema_version() -> u8 {
+    LOG_SCHEMA_VERSION
+}
+
+// Машиночитаемая строка ошибки: `ERR v1 code=<u64> ix=<tag> reason="<short>"`.
+// code — номер NetworkingError для своих ошибок и u64-код ProgramError для встроенных,
+// ix — первый байт данных инструкции (тег варианта) или `?`, если данных нет.
+pub fn format_error_log(instruction_tag: Option<u8>, error: &ProgramError) -> String {
+    let (code, reason) = match error {
+        ProgramError::Custom(code) => (
+            *code as u64,
+            NetworkingError::from_code(*code)
+                .map(|e| e.message().to_string())
+                .unwrap_or_else(|| error.to_string()),
+        ),
+        _ => (u64::from(error.clone()), error.to_string()),
+    };
+    let instruction_tag = instruction_tag.map_or("?".to_string(), |tag| tag.to_string());
+    format!(
+        "ERR v{} code={} ix={} reason=\"{}\"",
+        LOG_SCHEMA_VERSION,
+        code,
+        instruction_tag,
+        reason.replace('"', "'")
+    )
+}
+
+// Записывает new в field, только если значение отличается; true — было изменение
+pub fn set_if_changed<T: PartialEq>(field: &mut T, new: T) -> bool {
+    if *field == new {
+        return false;
+    }
+    *field = new;
+    true
+}
+
+// Текущее время из Clock; вместо непрозрачной ошибки sysvar возвращает ClockUnavailable
+pub fn current_timestamp() -> Result<i64, ProgramError> {
+    Clock::get().map(|clock| clock.unix_timestamp).map_err(|e| {
+        msg!("Clock sysvar unavailable: {}", e);
+        NetworkingError::ClockUnavailable.into()
+    })
+}
+
+// Счётчик в состоянии программы. Поле закрыто, поэтому сырого `+=` нет: значение
+// меняется только через increment, который насыщается на максимуме T, а не
+// переполняется и не заворачивается. В аккаунте лежит так же, как T.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counter<T>(T);
+
+impl<T: BorshSerialize> BorshSerialize for Counter<T> {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.0.serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Counter<T> {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        T::deserialize(buf).map(Counter)
+    }
+}
+
+impl<T: Copy + SaturatingAdd + One> Counter<T> {
+    pub fn new(value: T) -> Self {
+        Counter(value)
+    }
+
+    pub fn get(&self) -> T {
+        self.0
+    }
+
+    pub fn increment(&mut self) {
+        self.0 = self.0.saturating_add(&T::one());
+    }
+}
+
+// Каноническая форма тега: без пробелов по краям, ASCII в нижнем регистре,
+// только буквы, цифры и '_', длина MIN_TAG_LEN..=MAX_TAG_LEN.
+// "Rust", " rust" и "RUST" дают одни и те же байты.
+pub fn normalize_tag(tag: &str) -> Result<String, NetworkingError> {
+    let tag = tag.trim();
+    if tag.len() < MIN_TAG_LEN
+        || tag.len() > MAX_TAG_LEN
+        || !tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    {
+        return Err(NetworkingError::InvalidTag);
+    }
+    Ok(tag.to_ascii_lowercase())
+}
+
+// Нормализует теги поста и убирает повторы
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, ProgramError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = normalize_tag(&tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_POST_TAGS {
+        return Err(NetworkingError::TooManyTags.into());
+    }
+    Ok(normalized)
+}
+
+// Вложения поста: не больше MAX_POST_ATTACHMENTS, URI с известной схемой,
+// не длиннее MAX_ATTACHMENT_URI_LEN и без пробелов и управляющих символов
+pub fn validate_attachments(attachments: &[Attachment]) -> ProgramResult {
+    if attachments.len() > MAX_POST_ATTACHMENTS {
+        return Err(NetworkingError::TooManyAttachments.into());
+    }
+    for (i, attachment) in attachments.iter().enumerate() {
+        // Клиенты с ошибками повторов дописывают один и тот же URI несколько раз
+        if attachments[..i]
+            .iter()
+            .any(|other| other.uri == attachment.uri)
+        {
+            return Err(NetworkingError::DuplicateAttachmentUri.into());
+        }
+        let uri = attachment.uri.as_str();
+        let path = ["https://", "ipfs://", "ar://"]
+            .iter()
+            .find_map(|scheme| uri.strip_prefix(scheme));
+        let valid = match path {
+            Some(path) => {
+                !path.is_empty()
+                    && uri.len() <= MAX_ATTACHMENT_URI_LEN
+                    && uri.bytes().all(|b| b.is_ascii_graphic())
+            }
+            None => false,
+        };
+        if !valid {
+            return Err(NetworkingError::InvalidAttachmentUri.into());
+        }
+    }
+    Ok(())
+}
+
+// Домен рабочей почты в нижнем регистре: метки из букв, цифр и '-', минимум одна точка
+pub fn normalize_domain(domain: &str) -> Result<String, NetworkingError> {
+    let domain = domain.trim().to_ascii_lowercase();
+    let labels_valid = domain.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    });
+    if domain.len() > MAX_DOMAIN_LEN || !domain.contains('.') || !labels_valid {
+        return Err(NetworkingError::InvalidDomain);
+    }
+    Ok(domain)
+}
+
+// Друзья друзей, с которыми пользователь ещё не связан (рекомендации второго круга)
+pub fn friends_of_friends(
+    profile: &UserProfile,
+    friend_profiles: &HashMap<Pubkey, UserProfile>,
+) -> HashSet<Pubkey> {
+    profile
+        .friends
+        .iter()
+        .filter_map(|friend| friend_profiles.get(friend))
+        .flat_map(|friend_profile| friend_profile.friends.iter())
+        .filter(|candidate| **candidate != profile.address && !profile.friends.contains(candidate))
+        .copied()
+        .collect()
+}
+
+// Число общих друзей с каждым кандидатом, по убыванию (при равенстве — по ключу)
+pub fn rank_by_overlap(
+    me: &UserProfile,
+    candidates: &[(Pubkey, &UserProfile)],
+) -> Vec<(Pubkey, usize)> {
+    let mut ranked: Vec<(Pubkey, usize)> = candidates
+        .iter()
+        .map(|(key, candidate)| (*key, me.friends.intersection(&candidate.friends).count()))
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+// Доля друзей с NFT среди тех, чьи профили удалось найти; 0.0, если таких нет
+pub fn nft_friend_ratio(
+    profile: &UserProfile,
+    friend_profiles: &HashMap<Pubkey, UserProfile>,
+) -> f64 {
+    let resolved: Vec<&UserProfile> = profile
+        .friends
+        .iter()
+        .filter_map(|friend| friend_profiles.get(friend))
+        .collect();
+    if resolved.is_empty() {
+        return 0.0;
+    }
+    let holders = resolved.iter().filter(|friend| friend.nft_owned).count();
+    holders as f64 / resolved.len() as f64
+}
+
+// Плотность графа дружбы: фактические рёбра / возможные n*(n-1)/2
+pub fn graph_density(profiles: &[&UserProfile]) -> f64 {
+    let n = profiles.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let mut edges = 0usize;
+    for (i, a) in profiles.iter().enumerate() {
+        for b in &profiles[i + 1..] {
+            if a.friends.contains(&b.address) || b.friends.contains(&a.address) {
+                edges += 1;
+            }
+        }
+    }
+    edges as f64 / (n * (n - 1) / 2) as f64
+}
+
+// Список смежности графа дружбы; учитываются только рёбра между профилями из входного набора
+pub fn build_adjacency(profiles: &[&UserProfile]) -> HashMap<Pubkey, HashSet<Pubkey>> {
+    let mut adjacency: HashMap<Pubkey, HashSet<Pubkey>> = profiles
+        .iter()
+        .map(|profile| (profile.address, HashSet::new()))
+        .collect();
+    for profile in profiles {
+        for friend in &profile.friends {
+            if *friend != profile.address && adjacency.contains_key(friend) {
+                adjacency
+                    .entry(profile.address)
+                    .or_default()
+                    .insert(*friend);
+                adjacency
+                    .entry(*friend)
+                    .or_default()
+                    .insert(profile.address);
+            }
+        }
+    }
+    adjacency
+}
+
+// Держит ли токен-аккаунт NFT участника: аккаунт программы токенов с нужным минтом,
+// владелец — профиль, баланс не нулевой. Закрытый или переназначенный аккаунт не держит.
+pub fn holds_membership_nft(
+    nft: &MembershipNft,
+    owner: &Pubkey,
+    token_account: &AccountInfo,
+) -> bool {
+    if token_account.key != &nft.token_account || token_account.owner != &spl_token::id() {
+        return false;
+    }
+    let data = match token_account.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+    match spl_token::state::Account::unpack(&data) {
+        Ok(account) => account.mint == nft.mint && account.owner == *owner && account.amount > 0,
+        Err(_) => false,
+    }
+}
+
+// Все PDA выводятся от program_id вызова, а не от зашитого адреса
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+// Сид аватара-заглушки; зависит только от адреса, поэтому не меняется при обновлениях профиля
+pub fn identicon_seed(address: &Pubkey) -> [u8; 8] {
+    hashv(&[address.as_ref()]).to_bytes()[..8]
+        .try_into()
+        .unwrap()
+}
+
+// Симметричная сетка 5x5 в SVG: цвет из первых трёх байт сида, клетки левых
+// трёх колонок из следующих 15 бит, правые колонки — зеркало
+pub fn identicon_svg(seed: [u8; 8]) -> String {
+    let color = format!("#{:02x}{:02x}{:02x}", seed[0], seed[1], seed[2]);
+    let bits = u64::from_le_bytes(seed) >> 24;
+    let mut svg = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 5 5">"#);
+    for row in 0..5 {
+        for col in 0..3 {
+            if (bits >> (row * 3 + col)) & 1 == 0 {
+                continue;
+            }
+            let columns: &[u64] = if col == 2 { &[2] } else { &[col, 4 - col] };
+            for x in columns {
+                svg.push_str(&format!(
+                    r#"<rect x="{}" y="{}" width="1" height="1" fill="{}"/>"#,
+                    x, row, color
+                ));
+            }
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+// Общая доска анонимных вопросов (PDA [b"anonymous_board"]); её адрес служит
+// автором-заглушкой нераскрытых постов
+pub fn anonymous_board_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ANONYMOUS_BOARD_SEED], program_id)
+}
+
+pub fn anonymous_commitment(author: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[author.as_ref(), salt]).to_bytes()
+}
+
+// Отметка индексатора (PDA [b"indexer", indexer])
+pub fn indexer_checkpoint_address(program_id: &Pubkey, indexer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[INDEXER_SEED, indexer.as_ref()], program_id)
+}
+
+// Архив профиля на момент timestamp (PDA [b"snapshot", owner, timestamp])
+pub fn snapshot_address(program_id: &Pubkey, owner: &Pubkey, timestamp: i64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SNAPSHOT_SEED, owner.as_ref(), &timestamp.to_le_bytes()],
+        program_id,
+    )
+}
+
+// Длина самой длинной простой цепочки дружбы от start, не глубже max_depth.
+// Перебор с возвратом: вершины текущего пути не посещаются повторно, поэтому циклы не зацикливают.
+pub fn longest_path_from(
+    start: &Pubkey,
+    adjacency: &HashMap<Pubkey, HashSet<Pubkey>>,
+    max_depth: u32,
+) -> u32 {
+    fn walk(
+        node: &Pubkey,
+        adjacency: &HashMap<Pubkey, HashSet<Pubkey>>,
+        on_path: &mut HashSet<Pubkey>,
+        depth: u32,
+        max_depth: u32,
+    ) -> u32 {
+        if depth == max_depth {
+            return depth;
+        }
+        let mut longest = depth;
+        for next in adjacency.get(node).into_iter().flatten() {
+            if on_path.insert(*next) {
+                longest = longest.max(walk(next, adjacency, on_path, depth + 1, max_depth));
+                on_path.remove(next);
+            }
+        }
+        longest
+    }
+
+    let mut on_path = HashSet::from([*start]);
+    walk(start, adjacency, &mut on_path, 0, max_depth)
+}
+
+// Глобальная конфигурация программы (PDA [b"config"])
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct NetworkConfig {
+    pub is_initialized: bool,
+    pub admin: Pubkey,
+    pub oracles: Vec<Pubkey>,
+    pub repairs_enabled: bool,
+    pub membership_friend_threshold: u16,
+}
+
+impl NetworkConfig {
+    pub const LEN: usize = 1 + 32 + 4 + 32 * MAX_ORACLES + 1 + 2;
+
+    pub fn new(admin: Pubkey, oracles: Vec<Pubkey>) -> Self {
+        NetworkConfig {
+            is_initialized: true,
+            admin,
+            oracles,
+            repairs_enabled: false,
+            membership_friend_threshold: NFT_FRIEND_THRESHOLD as u16,
+        }
+    }
+
+    // Конфигурации, созданные до появления поля, хранят 0 — для них действует NFT_FRIEND_THRESHOLD
+    pub fn membership_threshold(&self) -> usize {
+        match self.membership_friend_threshold {
+            0 => NFT_FRIEND_THRESHOLD,
+            threshold => threshold as usize,
+        }
+    }
+
+    pub fn is_oracle(&self, key: &Pubkey) -> bool {
+        self.oracles.contains(key)
+    }
+
+    pub fn is_admin(&self, key: &Pubkey) -> bool {
+        self.admin == *key
+    }
+}
+
+// Отметка внешнего индексатора (PDA [b"indexer", indexer]), до какого nonce и слота
+// он дошёл; по ней координируются реплики. Сама программа отметки не читает.
+// Аккаунт создаёт и закрывает админ, так что существующие PDA и есть реестр индексаторов.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct IndexerCheckpoint {
+    pub is_initialized: bool,
+    pub indexer: Pubkey,
+    pub last_nonce_seen: u64,
+    pub last_slot: u64,
+}
+
+impl IndexerCheckpoint {
+    pub const LEN: usize = 1 + 32 + 8 + 8;
+
+    pub fn new(indexer: Pubkey) -> Self {
+        IndexerCheckpoint {
+            is_initialized: true,
+            indexer,
+            last_nonce_seen: 0,
+            last_slot: 0,
+        }
+    }
+
+    // Отметка только растёт: откат любой из величин отклоняется, повтор той же — допустим
+    pub fn advance(&mut self, last_nonce_seen: u64, last_slot: u64) -> ProgramResult {
+        if last_nonce_seen < self.last_nonce_seen || last_slot < self.last_slot {
+            msg!(
+                "CheckpointRegression: nonce {} -> {}, slot {} -> {}",
+                self.last_nonce_seen,
+                last_nonce_seen,
+                self.last_slot,
+                last_slot
+            );
+            return Err(NetworkingError::CheckpointRegression.into());
+        }
+        self.last_nonce_seen = last_nonce_seen;
+        self.last_slot = last_slot;
+        Ok(())
+    }
+}
+
+// Пост анонимной доски. Индекс сквозной по доске и не сдвигается при вытеснении,
+// author до раскрытия — адрес доски.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AnonymousPost {
+    pub index: u64,
+    pub author: Pubkey,
+    pub content: String,
+    pub kind: PostKind,
+    pub comments: Vec<Comment>,
+    pub timestamp: i64,
+}
+
+impl AnonymousPost {
+    // Автор, текст и quote_count комментария
+    const MAX_COMMENT_LEN: usize = 32 + 4 + MAX_ANONYMOUS_TEXT_LEN + 4;
+    // Индекс, автор, текст, PostKind::Anonymous, комментарии и время
+    pub const MAX_LEN: usize = 8
+        + 32
+        + 4
+        + MAX_ANONYMOUS_TEXT_LEN
+        + 1
+        + 32
+        + 4
+        + MAX_BOARD_COMMENTS_PER_POST * Self::MAX_COMMENT_LEN
+        + 8;
+}
+
+// Доска анонимных вопросов: не больше MAX_BOARD_POSTS постов, новый вытесняет самый
+// старый, поэтому аккаунт фиксированного размера никогда не переполняется
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AnonymousBoard {
+    pub is_initialized: bool,
+    pub next_index: u64,
+    pub posts: Vec<AnonymousPost>,
+}
+
+impl AnonymousBoard {
+    pub const LEN: usize = 1 + 8 + 4 + MAX_BOARD_POSTS * AnonymousPost::MAX_LEN;
+
+    pub fn new() -> Self {
+        AnonymousBoard {
+            is_initialized: true,
+            next_index: 0,
+            posts: Vec::new(),
+        }
+    }
+
+    // Возвращает индекс нового поста; sentinel — адрес доски
+    pub fn post(
+        &mut self,
+        sentinel: Pubkey,
+        content: String,
+        commitment: [u8; 32],
+        timestamp: i64,
+    ) -> Result<u64, ProgramError> {
+        if content.len() > MAX_ANONYMOUS_TEXT_LEN {
+            return Err(NetworkingError::AnonymousTextTooLong.into());
+        }
+        if self.posts.len() >= MAX_BOARD_POSTS {
+            let evicted = self.posts.remove(0);
+            msg!("AnonymousPostEvicted: index={}", evicted.index);
+        }
+        let index = self.next_index;
+        self.next_index = index
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.posts.push(AnonymousPost {
+            index,
+            author: sentinel,
+            content,
+            kind: PostKind::Anonymous { commitment },
+            comments: Vec::new(),
+            timestamp,
+        });
+        Ok(index)
+    }
+
+    // Анонимен только вопрос: комментарии подписаны профилем
+    pub fn comment(&mut self, index: u64, author: Pubkey, content: String) -> ProgramResult {
+        if content.len() > MAX_ANONYMOUS_TEXT_LEN {
+            return Err(NetworkingError::AnonymousTextTooLong.into());
+        }
+        let post = self.find_mut(index)?;
+        if post.comments.len() >= MAX_BOARD_COMMENTS_PER_POST {
+            return Err(NetworkingError::CommentLimitReached.into());
+        }
+        post.comments.push(Comment::new(author, content));
+        Ok(())
+    }
+
+    // Автор раскрывает себя солью: sha256(author || salt) должен совпасть с commitment
+    pub fn reveal(
+        &mut self,
+        index: u64,
+        sentinel: &Pubkey,
+        author: Pubkey,
+        salt: &[u8; 32],
+    ) -> ProgramResult {
+        let post = self.find_mut(index)?;
+        if post.author != *sentinel {
+            return Err(NetworkingError::AlreadyRevealed.into());
+        }
+        match post.kind {
+            PostKind::Anonymous { commitment }
+                if anonymous_commitment(&author, salt) == commitment =>
+            {
+                post.author = author;
+                Ok(())
+            }
+            _ => Err(NetworkingError::RevealMismatch.into()),
+        }
+    }
+
+    fn find_mut(&mut self, index: u64) -> Result<&mut AnonymousPost, ProgramError> {
+        self.posts
+            .iter_mut()
+            .find(|post| post.index == index)
+            .ok_or_else(|| NetworkingError::PostNotFound.into())
+    }
+}
+
+impl Default for AnonymousBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Доска создаётся одним CPI create_account, а он не выделяет больше этого
+const _: () =
+    assert!(AnonymousBoard::LEN <= solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE);
+
+// Операции ремонта повреждённого аккаунта для AdminRepairAccount.
+// Набор намеренно узкий: произвольная запись байтов невозможна.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum RepairOp {
+    // Обнуляет данные начиная с from (устаревший хвост после сжатия профиля)
+    ZeroTail { from: u32 },
+    // Перезаписывает первый байт (is_initialized у профиля), только 0 или 1
+    SetVersionByte { v: u8 },
+    // Уменьшает аккаунт до len байт
+    TruncateTo { len: u32 },
+}
+
+// Проверяет весь список операций до применения: каждая должна укладываться
+// в размер аккаунта с учётом предыдущих TruncateTo, а байт версии - быть флагом
+pub fn validate_repair_ops(ops: &[RepairOp], data_len: usize) -> ProgramResult {
+    let mut len = data_len;
+    for op in ops {
+        match *op {
+            RepairOp::ZeroTail { from } if from as usize <= len => {}
+            RepairOp::SetVersionByte { v } if len > 0 && v <= 1 => {}
+            RepairOp::TruncateTo { len: new_len } if (new_len as usize) <= len => {
+                len = new_len as usize;
+            }
+            _ => {
+                msg!("Repair op {:?} is not valid for a {}-byte account", op, len);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl RepairOp {
+    fn apply(&self, account: &AccountInfo) -> ProgramResult {
+        match *self {
+            RepairOp::ZeroTail { from } => {
+                account.try_borrow_mut_data()?[from as usize..].fill(0);
+            }
+            RepairOp::SetVersionByte { v } => {
+                account.try_borrow_mut_data()?[0] = v;
+            }
+            RepairOp::TruncateTo { len } => account.realloc(len as usize, false)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum ProfessionalNetworkingInstruction {
+    CreateUserProfile {
+        name: String,
+        bio: String,
+        profile_picture: String,
+        referred_by: Option<Pubkey>,
+    },
+    SendFriendRequest {
+        friend_address: Pubkey,
+    },
+    AcceptFriendRequest {
+        friend_address: Pubkey,
+    },
+    WritePost {
+        content: String,
+        tags: Vec<String>,
+        attachments: Vec<Attachment>,
+    },
+    AddComment {
+        post_author: Pubkey,
+        post_index: usize,
+        content: String,
+    },
+    InitializeConfig {
+        oracles: Vec<Pubkey>,
+    },
+    SetOracles {
+        oracles: Vec<Pubkey>,
+    },
+    AttestExternalIdentity {
+        platform: u8,
+        handle_hash: [u8; 32],
+    },
+    RemoveExternalIdentity {
+        platform: u8,
+    },
+    ClaimReferral,
+    RecordImpression {
+        post_author: Pubkey,
+        post_index: usize,
+    },
+    SetTheme {
+        theme: u8,
+    },
+    QuoteComment {
+        post_author: Pubkey,
+        post_index: usize,
+        comment_index: usize,
+        commentary: Option<String>,
+    },
+    DeactivateProfile,
+    ReactivateProfile,
+    ReportUser {
+        target: Pubkey,
+        reason_code: u8,
+    },
+    RenewMembership,
+    VerifyDomain {
+        target: Pubkey,
+        domain: String,
+    },
+    AddSection {
+        title: String,
+        body: String,
+    },
+    UpdateSection {
+        index: u8,
+        title: String,
+        body: String,
+    },
+    RemoveSection {
+        index: u8,
+    },
+    ReorderSections {
+        new_order: Vec<u8>,
+    },
+    SetRetentionPolicy {
+        max_retained_posts: u16,
+    },
+    SetRepairsEnabled {
+        enabled: bool,
+    },
+    AdminRepairAccount {
+        patch: Vec<RepairOp>,
+    },
+    SnapshotProfile {
+        timestamp: i64,
+    },
+    ReconcileMembership {
+        profile: Pubkey,
+    },
+    SetEncryptionKey {
+        key: [u8; 32],
+    },
+    SetPrivateContact {
+        blob: Vec<u8>,
+    },
+    ShareContactWith {
+        peer: Pubkey,
+        blob: Vec<u8>,
+    },
+    SetTimezone {
+        offset: i8,
+    },
+    SetAvailability {
+        start: u8,
+        end: u8,
+    },
+    SetMembershipThreshold {
+        threshold: u16,
+    },
+    RegisterIndexer {
+        indexer: Pubkey,
+    },
+    SetIndexerCheckpoint {
+        last_nonce_seen: u64,
+        last_slot: u64,
+    },
+    RetireIndexer,
+    InitializeAnonymousBoard,
+    WriteAnonymousPost {
+        content: String,
+        commitment: [u8; 32],
+    },
+    CommentAnonymousPost {
+        post_index_on_board: u64,
+        content: String,
+    },
+    RevealAnonymousPost {
+        post_index_on_board: u64,
+        salt: [u8; 32],
+    },
+}
+
+entrypoint!(process_instruction);
+
+// Любая ошибка обработчика логируется одной строкой в формате format_error_log
+// прямо перед возвратом; клиенты разбирают её через client::parse_error_log
+fn process_instruction<'a>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let result = dispatch_instruction(program_id, accounts, instruction_data);
+    if let Err(error) = &result {
+        msg!(
+            "{}",
+            format_error_log(instruction_data.first().copied(), error)
+        );
+    }
+    result
+}
+
+fn dispatch_instruction<'a>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = ProfessionalNetworkingInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let account_info_iter = &mut accounts.iter();
+
+    let user_account = next_account_info(account_info_iter)?;
+    // Действующий аккаунт — профиль программы, подписывающий каждую инструкцию,
+    // кроме сверки членства, которую может запустить любой крэнк
+    if !matches!(
+        instruction,
+        ProfessionalNetworkingInstruction::ReconcileMembership { .. }
+    ) {
+        require_signer(user_account)?;
+    }
+
+    match instruction {
+        ProfessionalNetworkingInstruction::CreateUserProfile {
+            name,
+            bio,
+            profile_picture,
+            referred_by,
+        } => {
+            if referred_by == Some(*user_account.key) {
+                return Err(NetworkingError::SelfReferral.into());
+            }
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            // Повторное создание сбросило бы referral_claimed и остальное состояние
+            if user_data.first().copied().unwrap_or(0) != 0 {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            let mut new_user_profile =
+                UserProfile::new(name, bio, profile_picture, *user_account.key);
+            new_user_profile.referred_by = referred_by;
+            let serialized_data = new_user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::SendFriendRequest { friend_address } => {
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            user_profile.ensure_active()?;
+
+            user_profile.ensure_room(&MutationKind::AddFriend, user_data.len())?;
+            user_profile.send_friend_request(friend_address)?;
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+        ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address } => {
+            let mut borrows = AccountBorrowGuard::default();
+            check_owner(user_account, program_id)?;
+            let mut user_data = borrows.borrow_mut_data(user_account)?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            user_profile.ensure_active()?;
+
+            let mint_nft = user_profile.reaches_nft_threshold_with(&friend_address);
+            if mint_nft {
+                user_profile.ensure_room(&MutationKind::RecordMembershipNft, user_data.len())?;
+                let nft_mint_account = next_account_info(account_info_iter)?;
+                let nft_account = next_account_info(account_info_iter)?;
+                let payer = next_account_info(account_info_iter)?;
+                let system_program = next_account_info(account_info_iter)?;
+                let token_program = next_account_info(account_info_iter)?;
+                let rent_sysvar = next_account_info(account_info_iter)?;
+
+                create_nft(
+                    nft_mint_account,
+                    nft_account,
+                    user_account,
+                    payer,
+                    system_program,
+                    token_program,
+                    rent_sysvar,
+                )?;
+
+                user_profile.grant_membership(current_timestamp()?);
+                user_profile.membership_nft = Some(MembershipNft {
+                    mint: *nft_mint_account.key,
+                    token_account: *nft_account.key,
+                });
+            }
+
+            let friend_account = next_account_info(account_info_iter)?;
+            if friend_account.key != &friend_address {
+                return Err(ProgramError::InvalidArgument);
+            }
+            check_owner(friend_account, program_id)?;
+            let mut friend_data = borrows.borrow_mut_data(friend_account)?;
+            let mut friend_profile = load_profile(friend_account, &friend_data)?;
+            // Деактивированный профиль не дружит ни с какой стороны
+            friend_profile.ensure_active()?;
+
+            // Повторное принятие (например, оба приняли заявки друг друга в одном слоте) — no-op.
+            // Проверяется до ensure_room, чтобы проходить и на заполненном аккаунте.
+            let user_gains = !user_profile.friends.contains(&friend_address);
+            let friend_gains = !friend_profile.friends.contains(user_account.key);
+            if !user_gains && !friend_gains && !mint_nft {
+                msg!("AlreadyFriends: {}", friend_address);
+                return Ok(());
+            }
+            if user_gains {
+                user_profile.ensure_room(&MutationKind::AddFriend, user_data.len())?;
+            }
+            if friend_gains {
+                friend_profile.ensure_room(&MutationKind::AddFriend, friend_data.len())?;
+            }
+            user_profile.link_friends(&mut friend_profile)?;
+
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            let serialized_data = friend_profile.try_to_vec()?;
+            friend_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::WritePost {
+            content,
+            tags,
+            attachments,
+        } => {
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            user_profile.ensure_active()?;
+
+            let now = current_timestamp()?;
+            if !user_profile.can_write_post(now) {
+                return Err(NetworkingError::NotEligible.into());
+            }
+
+            let tags = normalize_tags(tags)?;
+            validate_attachments(&attachments)?;
+            user_profile.ensure_room(
+                &MutationKind::AddPost {
+                    content_len: content.len(),
+                    tags: tags.len(),
+                    attachments: attachments.len(),
+                },
+                user_data.len(),
+            )?;
+            user_profile.add_post(*user_account.key, content, tags, attachments, now);
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::AddComment {
+            post_author,
+            post_index,
+            content,
+        } => {
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            user_profile.ensure_active()?;
+
+            if !user_profile.can_comment(current_timestamp()?) {
+                return Err(NetworkingError::NotEligible.into());
+            }
+            user_profile.ensure_room(
+                &MutationKind::AddComment {
+                    content_len: content.len(),
+                },
+                user_data.len(),
+            )?;
+
+            user_profile.add_comment(post_author, post_index, *user_account.key, content)?;
+            user_profile.serialize(&mut &mut user_data[..])?;
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::InitializeConfig { oracles } => {
+            if oracles.len() > MAX_ORACLES {
+                return Err(NetworkingError::TooManyOracles.into());
+            }
+            let config_account = next_account_info(account_info_iter)?;
+            let payer = next_account_info(account_info_iter)?;
+            let system_program = next_account_info(account_info_iter)?;
+            let program_data = next_account_info(account_info_iter)?;
+
+            // Иначе админом стал бы первый, кто успел вызвать инструкцию после деплоя
+            if upgrade_authority(program_data, program_id)? != Some(*user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+            let (config_key, bump) = config_address(program_id);
+            if config_account.key != &config_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if !config_account.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+
+            create_program_account(
+                payer,
+                config_account,
+                system_program,
+                program_id,
+                NetworkConfig::LEN,
+                &[CONFIG_SEED, &[bump]],
+            )?;
+
+            let config = NetworkConfig::new(*user_account.key, oracles);
+            config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::SetOracles { oracles } => {
+            if oracles.len() > MAX_ORACLES {
+                return Err(NetworkingError::TooManyOracles.into());
+            }
+            let config_account = next_account_info(account_info_iter)?;
+            let mut config = load_config(config_account, program_id)?;
+
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+
+            config.oracles = oracles;
+            config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::AttestExternalIdentity {
+            platform,
+            handle_hash,
+        } => {
+            let config_account = next_account_info(account_info_iter)?;
+            let profile_account = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+
+            if !config.is_oracle(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+
+            check_owner(profile_account, program_id)?;
+            let mut profile_data = profile_account.try_borrow_mut_data()?;
+            let mut profile = load_profile(profile_account, &profile_data)?;
+
+            profile.attest_external_identity(ExternalIdentity {
+                platform,
+                handle_hash,
+                attested_by: *user_account.key,
+                attested_at: current_timestamp()?,
+            })?;
+            store_profile(&mut profile_data, &profile)?;
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::RemoveExternalIdentity { platform } => {
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+
+            user_profile.remove_external_identity(platform)?;
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::ClaimReferral => {
+            let referrer_account = next_account_info(account_info_iter)?;
+
+            let mut borrows = AccountBorrowGuard::default();
+            check_owner(user_account, program_id)?;
+            let mut user_data = borrows.borrow_mut_data(user_account)?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            if user_profile.referred_by != Some(*referrer_account.key) {
+                return Err(NetworkingError::ReferrerMismatch.into());
+            }
+            check_owner(referrer_account, program_id)?;
+            let mut referrer_data = borrows.borrow_mut_data(referrer_account)?;
+            let mut referrer_profile = load_profile(referrer_account, &referrer_data)?;
+
+            user_profile.claim_referral(&mut referrer_profile)?;
+
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            let serialized_data = referrer_profile.try_to_vec()?;
+            referrer_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::RecordImpression {
+            post_author,
+            post_index,
+        } => {
+            let profile_account = next_account_info(account_info_iter)?;
+            check_owner(profile_account, program_id)?;
+            let mut profile_data = profile_account.try_borrow_mut_data()?;
+            let mut profile = load_profile(profile_account, &profile_data)?;
+
+            let post = profile
+                .posts
+                .get_mut(&post_author)
+                .and_then(|posts| posts.get_mut(post_index))
+                .ok_or(NetworkingError::PostNotFound)?;
+            if post.record_impression(user_account.key, post_index)? {
+                store_profile(&mut profile_data, &profile)?;
+            }
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::SetTheme { theme } => {
+            update_own_setting(user_account, program_id, |profile| profile.set_theme(theme))
+        }
+
+        ProfessionalNetworkingInstruction::QuoteComment {
+            post_author,
+            post_index,
+            comment_index,
+            commentary,
+        } => {
+            let source_account = next_account_info(account_info_iter)?;
+
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+            user_profile.ensure_active()?;
+
+            let now = current_timestamp()?;
+            if !user_profile.can_write_post(now) {
+                return Err(NetworkingError::NotEligible.into());
+            }
+            user_profile.ensure_room(
+                &MutationKind::AddQuotePost {
+                    commentary_len: commentary.as_ref().map_or(0, String::len),
+                },
+                user_data.len(),
+            )?;
+
+            if source_account.key == user_account.key {
+                let kind = user_profile.quote_comment(post_author, post_index, comment_index)?;
+                user_profile.add_quote_post(*user_account.key, commentary, kind, now);
+            } else {
+                check_owner(source_account, program_id)?;
+                let mut source_data = source_account.try_borrow_mut_data()?;
+                let mut source_profile = load_profile(source_account, &source_data)?;
+
+                let kind = source_profile.quote_comment(post_author, post_index, comment_index)?;
+                user_profile.add_quote_post(*user_account.key, commentary, kind, now);
+
+                let serialized_data = source_profile.try_to_vec()?;
+                source_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            }
+
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::DeactivateProfile
+        | ProfessionalNetworkingInstruction::ReactivateProfile => {
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+
+            user_profile.deactivated = matches!(
+                instruction,
+                ProfessionalNetworkingInstruction::DeactivateProfile
+            );
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::ReportUser {
+            target,
+            reason_code,
+        } => {
+            let target_account = next_account_info(account_info_iter)?;
+            if target_account.key != &target {
+                return Err(ProgramError::InvalidArgument);
+            }
+            check_owner(target_account, program_id)?;
+            let mut target_data = target_account.try_borrow_mut_data()?;
+            let mut target_profile = load_profile(target_account, &target_data)?;
+
+            target_profile.report(*user_account.key, reason_code)?;
+            store_profile(&mut target_data, &target_profile)?;
+
+            Ok(())
+        }
+
+        // Аккаунты: профиль, конфигурация, затем профили друзей для подсчёта подтверждённых
+        ProfessionalNetworkingInstruction::RenewMembership => {
+            let config_account = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+            check_owner(user_account, program_id)?;
+            let mut user_data = user_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(user_account, &user_data)?;
+
+            let confirmed =
+                count_confirmed_friends(&user_profile, account_info_iter.as_slice(), program_id)?;
+            user_profile.renew_membership(
+                current_timestamp()?,
+                confirmed,
+                config.membership_threshold(),
+            )?;
+            let serialized_data = user_profile.try_to_vec()?;
+            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::VerifyDomain { target, domain } => {
+            let config_account = next_account_info(account_info_iter)?;
+            let target_account = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+
+            if target_account.key != &target {
+                return Err(ProgramError::InvalidArgument);
+            }
+            check_owner(target_account, program_id)?;
+            let mut target_data = target_account.try_borrow_mut_data()?;
+            let mut target_profile = load_profile(target_account, &target_data)?;
+
+            target_profile.verified_domain = Some(normalize_domain(&domain)?);
+            store_profile(&mut target_data, &target_profile)?;
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::AddSection { title, body } => {
+            update_own_profile(user_account, program_id, |profile| {
+                profile.add_section(title, body)
+            })
+        }
+
+        ProfessionalNetworkingInstruction::UpdateSection { index, title, body } => {
+            update_own_profile(user_account, program_id, |profile| {
+                profile.update_section(index, title, body)
+            })
         }
-    }
 
-    pub fn add_comment(&mut self, author: Pubkey, content: String) {
-        self.comments.push(Comment::new(author, content));
-    }
-}
+        ProfessionalNetworkingInstruction::RemoveSection { index } => {
+            update_own_profile(user_account, program_id, |profile| {
+                profile.remove_section(index)
+            })
+        }
 
-// Структура для профиля пользователя
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct UserProfile {
-    pub is_initialized: bool,
-    pub name: String,
-    pub bio: String,
-    pub profile_picture: String,
-    pub address: Pubkey,
-    pub friends: HashSet<Pubkey>,
-    pub nft_owned: bool,
-    pub posts: HashMap<Pubkey, Vec<Post>>,
-}
+        ProfessionalNetworkingInstruction::ReorderSections { new_order } => {
+            update_own_profile(user_account, program_id, |profile| {
+                profile.reorder_sections(&new_order)
+            })
+        }
 
-impl UserProfile {
-    pub fn new(name: String, bio: String, profile_picture: String, address: Pubkey) -> Self {
-        UserProfile {
-            is_initialized: true,
-            name,
-            bio,
-            profile_picture,
-            address,
-            friends: HashSet::new(),
-            nft_owned: false,
-            posts: HashMap::new(),
+        ProfessionalNetworkingInstruction::SetRetentionPolicy { max_retained_posts } => {
+            update_own_setting(user_account, program_id, |profile| {
+                profile.set_retention_policy(max_retained_posts)
+            })
         }
-    }
 
-    pub fn can_write_post(&self) -> bool {
-        self.nft_owned && self.friends.len() >= 5
-    }
+        ProfessionalNetworkingInstruction::SetRepairsEnabled { enabled } => {
+            let config_account = next_account_info(account_info_iter)?;
+            let mut config = load_config(config_account, program_id)?;
 
-    pub fn can_comment(&self) -> bool {
-        self.nft_owned && self.friends.len() >= 5
-    }
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
 
-    pub fn add_post(&mut self, author: Pubkey, content: String) {
-        let post = Post::new(author, content);
-        self.posts.entry(author).or_insert_with(Vec::new).push(post);
-    }
+            config.repairs_enabled = enabled;
+            config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
 
-    pub fn add_comment(
-        &mut self,
-        post_author: Pubkey,
-        post_index: usize,
-        comment_author: Pubkey,
-        content: String,
-    ) -> ProgramResult {
-        if let Some(posts) = self.posts.get_mut(&post_author) {
-            if let Some(post) = posts.get_mut(post_index) {
-                post.add_comment(comment_author, content);
-                return Ok(());
+            Ok(())
+        }
+
+        // Аварийный ремонт аккаунтов, которые перестали декодироваться
+        ProfessionalNetworkingInstruction::AdminRepairAccount { patch } => {
+            let config_account = next_account_info(account_info_iter)?;
+            let target_account = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+            if !config.repairs_enabled {
+                return Err(NetworkingError::RepairsDisabled.into());
+            }
+            check_owner(target_account, program_id)?;
+
+            validate_repair_ops(&patch, target_account.data_len())?;
+            msg!(
+                "AccountRepaired: admin={} target={} ops={:?}",
+                user_account.key,
+                target_account.key,
+                patch
+            );
+            for op in &patch {
+                op.apply(target_account)?;
             }
+
+            Ok(())
         }
-        Err(ProgramError::InvalidAccountData)
-    }
 
-    pub fn get_post_with_comments(&self, author: &Pubkey, post_index: usize) -> Option<&Post> {
-        self.posts.get(author)?.get(post_index)
-    }
-}
+        ProfessionalNetworkingInstruction::SnapshotProfile { timestamp } => {
+            let snapshot_account = next_account_info(account_info_iter)?;
+            let payer = next_account_info(account_info_iter)?;
+            let system_program = next_account_info(account_info_iter)?;
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum ProfessionalNetworkingInstruction {
-    CreateUserProfile {
-        name: String,
-        bio: String,
-        profile_picture: String,
-    },
-    SendFriendRequest {
-        friend_address: Pubkey,
-    },
-    AcceptFriendRequest {
-        friend_address: Pubkey,
-    },
-    WritePost {
-        content: String,
-    },
-    AddComment {
-        post_author: Pubkey,
-        post_index: usize,
-        content: String,
-    },
-}
+            if timestamp > current_timestamp()? {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let (snapshot_key, bump) = snapshot_address(program_id, user_account.key, timestamp);
+            if snapshot_account.key != &snapshot_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if !snapshot_account.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
 
-entrypoint!(process_instruction);
+            // Архив — точная копия профиля; инструкций, меняющих его, нет
+            check_owner(user_account, program_id)?;
+            let snapshot =
+                load_profile(user_account, &user_account.try_borrow_data()?)?.try_to_vec()?;
+            create_program_account(
+                payer,
+                snapshot_account,
+                system_program,
+                program_id,
+                snapshot.len(),
+                &[
+                    SNAPSHOT_SEED,
+                    user_account.key.as_ref(),
+                    &timestamp.to_le_bytes(),
+                    &[bump],
+                ],
+            )?;
+            snapshot_account
+                .try_borrow_mut_data()?
+                .copy_from_slice(&snapshot);
 
-fn process_instruction<'a>(
-    _program_id: &Pubkey,
-    accounts: &'a [AccountInfo<'a>],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = ProfessionalNetworkingInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+            Ok(())
+        }
 
-    let account_info_iter = &mut accounts.iter();
+        // Без подписи владельца: сверять может любой крэнк
+        ProfessionalNetworkingInstruction::ReconcileMembership { profile } => {
+            let profile_account = next_account_info(account_info_iter)?;
+            let token_account = next_account_info(account_info_iter)?;
+            if profile_account.key != &profile {
+                return Err(ProgramError::InvalidArgument);
+            }
 
-    let user_account = next_account_info(account_info_iter)?;
+            check_owner(profile_account, program_id)?;
+            let mut profile_data = profile_account.try_borrow_mut_data()?;
+            let mut user_profile = load_profile(profile_account, &profile_data)?;
+            let nft = user_profile.membership_nft.ok_or_else(|| {
+                msg!("No membership NFT recorded for {}", profile);
+                ProgramError::InvalidAccountData
+            })?;
+            if token_account.key != &nft.token_account {
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let holds_nft = holds_membership_nft(&nft, &profile, token_account);
+            let changed = user_profile.reconcile_membership(holds_nft);
+            msg!(
+                "MembershipReconciled: profile={} holds_nft={} changed={}",
+                profile,
+                holds_nft,
+                changed
+            );
+            if changed {
+                let serialized_data = user_profile.try_to_vec()?;
+                profile_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            }
 
-    match instruction {
-        ProfessionalNetworkingInstruction::CreateUserProfile {
-            name,
-            bio,
-            profile_picture,
-        } => {
-            let mut user_data = user_account.try_borrow_mut_data()?;
-            let new_user_profile = UserProfile::new(name, bio, profile_picture, *user_account.key);
-            let serialized_data = new_user_profile.try_to_vec()?;
-            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
             Ok(())
         }
 
-        ProfessionalNetworkingInstruction::SendFriendRequest { friend_address } => {
-            let mut user_data = user_account.try_borrow_mut_data()?;
-            let mut user_profile = UserProfile::try_from_slice(&user_data)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
+        ProfessionalNetworkingInstruction::SetEncryptionKey { key } => {
+            update_own_setting(user_account, program_id, |profile| {
+                Ok(set_if_changed(&mut profile.encryption_pubkey, Some(key)))
+            })
+        }
 
-            if user_profile.friends.contains(&friend_address) {
-                return Err(ProgramError::InvalidAccountData);
+        ProfessionalNetworkingInstruction::SetPrivateContact { blob } => {
+            update_own_setting(user_account, program_id, |profile| {
+                profile.set_private_contact(blob)
+            })
+        }
+
+        ProfessionalNetworkingInstruction::ShareContactWith { peer, blob } => {
+            let peer_account = next_account_info(account_info_iter)?;
+            if peer_account.key != &peer {
+                return Err(ProgramError::InvalidArgument);
             }
 
-            user_profile.friends.insert(friend_address);
-            let serialized_data = user_profile.try_to_vec()?;
-            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            check_owner(user_account, program_id)?;
+            let user_profile = load_profile(user_account, &user_account.try_borrow_data()?)?;
+            user_profile.ensure_active()?;
+            check_owner(peer_account, program_id)?;
+            let mut peer_data = peer_account.try_borrow_mut_data()?;
+            let mut peer_profile = load_profile(peer_account, &peer_data)?;
+
+            peer_profile.ensure_room(
+                &MutationKind::ShareContact {
+                    blob_len: blob.len(),
+                },
+                peer_data.len(),
+            )?;
+            peer_profile.receive_shared_contact(&user_profile, blob)?;
+            let serialized_data = peer_profile.try_to_vec()?;
+            peer_data[..serialized_data.len()].copy_from_slice(&serialized_data);
 
             Ok(())
         }
-        ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address } => {
-            let mut user_data = user_account.try_borrow_mut_data()?;
-            let mut user_profile = UserProfile::try_from_slice(&user_data)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
 
-            user_profile.friends.insert(friend_address);
-            if user_profile.friends.len() >= 5 && !user_profile.nft_owned {
-                let nft_mint_account = next_account_info(account_info_iter)?;
-                let nft_account = next_account_info(account_info_iter)?;
-                let system_program = next_account_info(account_info_iter)?;
-                let token_program = next_account_info(account_info_iter)?;
-                let rent_sysvar = next_account_info(account_info_iter)?;
+        ProfessionalNetworkingInstruction::SetTimezone { offset } => {
+            update_own_setting(user_account, program_id, |profile| {
+                profile.set_timezone(offset)
+            })
+        }
 
-                create_nft(
-                    nft_mint_account,
-                    nft_account,
-                    user_account,
-                    system_program,
-                    token_program,
-                    rent_sysvar,
-                )?;
+        ProfessionalNetworkingInstruction::SetAvailability { start, end } => {
+            update_own_setting(user_account, program_id, |profile| {
+                profile.set_availability(start, end)
+            })
+        }
 
-                user_profile.nft_owned = true;
+        ProfessionalNetworkingInstruction::SetMembershipThreshold { threshold } => {
+            if threshold == 0 {
+                return Err(ProgramError::InvalidArgument);
             }
-            let serialized_data = user_profile.try_to_vec()?;
-            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            let config_account = next_account_info(account_info_iter)?;
+            let mut config = load_config(config_account, program_id)?;
 
-            let friend_account = next_account_info(account_info_iter)?;
-            let mut friend_data = friend_account.try_borrow_mut_data()?;
-            let mut friend_profile = UserProfile::try_from_slice(&friend_data)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
 
-            friend_profile.friends.insert(*user_account.key);
-            let serialized_data = friend_profile.try_to_vec()?;
-            friend_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            config.membership_friend_threshold = threshold;
+            config.serialize(&mut &mut config_account.try_borrow_mut_data()?[..])?;
 
             Ok(())
         }
 
-        ProfessionalNetworkingInstruction::WritePost { content } => {
-            let mut user_data = user_account.try_borrow_mut_data()?;
-            let mut user_profile = UserProfile::try_from_slice(&user_data)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
+        ProfessionalNetworkingInstruction::RegisterIndexer { indexer } => {
+            let config_account = next_account_info(account_info_iter)?;
+            let checkpoint_account = next_account_info(account_info_iter)?;
+            let payer = next_account_info(account_info_iter)?;
+            let system_program = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
 
-            if !user_profile.can_write_post() {
-                return Err(ProgramError::InvalidAccountData);
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+            let (checkpoint_key, bump) = indexer_checkpoint_address(program_id, &indexer);
+            if checkpoint_account.key != &checkpoint_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if !checkpoint_account.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
             }
 
-            user_profile.add_post(*user_account.key, content);
-            let serialized_data = user_profile.try_to_vec()?;
-            user_data[..serialized_data.len()].copy_from_slice(&serialized_data);
+            create_program_account(
+                payer,
+                checkpoint_account,
+                system_program,
+                program_id,
+                IndexerCheckpoint::LEN,
+                &[INDEXER_SEED, indexer.as_ref(), &[bump]],
+            )?;
+            IndexerCheckpoint::new(indexer)
+                .serialize(&mut &mut checkpoint_account.try_borrow_mut_data()?[..])?;
 
             Ok(())
         }
 
-        ProfessionalNetworkingInstruction::AddComment {
-            post_author,
-            post_index,
-            content,
+        // Подписывает сам индексатор
+        ProfessionalNetworkingInstruction::SetIndexerCheckpoint {
+            last_nonce_seen,
+            last_slot,
         } => {
-            let mut user_data = user_account.try_borrow_mut_data()?;
-            let mut user_profile = UserProfile::try_from_slice(&user_data)
-                .map_err(|_| ProgramError::InvalidAccountData)?;
+            let checkpoint_account = next_account_info(account_info_iter)?;
+            let mut checkpoint = load_indexer_checkpoint(checkpoint_account, program_id)?;
 
-            if !user_profile.can_comment() {
-                return Err(ProgramError::InvalidAccountData);
+            if checkpoint.indexer != *user_account.key {
+                return Err(NetworkingError::Unauthorized.into());
             }
 
-            user_profile.add_comment(post_author, post_index, *user_account.key, content);
-            user_profile.serialize(&mut &mut user_data[..])?;
+            checkpoint.advance(last_nonce_seen, last_slot)?;
+            checkpoint.serialize(&mut &mut checkpoint_account.try_borrow_mut_data()?[..])?;
+
+            Ok(())
+        }
+
+        // Индексатор выведен из работы: аккаунт закрывается, аренда уходит recipient
+        ProfessionalNetworkingInstruction::RetireIndexer => {
+            let config_account = next_account_info(account_info_iter)?;
+            let checkpoint_account = next_account_info(account_info_iter)?;
+            let recipient = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+            load_indexer_checkpoint(checkpoint_account, program_id)?;
+
+            let rent = checkpoint_account.lamports();
+            **recipient.try_borrow_mut_lamports()? = recipient
+                .lamports()
+                .checked_add(rent)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+            **checkpoint_account.try_borrow_mut_lamports()? = 0;
+            checkpoint_account.try_borrow_mut_data()?.fill(0);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::InitializeAnonymousBoard => {
+            let config_account = next_account_info(account_info_iter)?;
+            let board_account = next_account_info(account_info_iter)?;
+            let payer = next_account_info(account_info_iter)?;
+            let system_program = next_account_info(account_info_iter)?;
+            let config = load_config(config_account, program_id)?;
+
+            if !config.is_admin(user_account.key) {
+                return Err(NetworkingError::Unauthorized.into());
+            }
+            let (board_key, bump) = anonymous_board_address(program_id);
+            if board_account.key != &board_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            if !board_account.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+
+            create_program_account(
+                payer,
+                board_account,
+                system_program,
+                program_id,
+                AnonymousBoard::LEN,
+                &[ANONYMOUS_BOARD_SEED, &[bump]],
+            )?;
+            AnonymousBoard::new().serialize(&mut &mut board_account.try_borrow_mut_data()?[..])?;
+
+            Ok(())
+        }
+
+        // Подписант — любой ключ, обычно одноразовый: профиль не нужен, иначе
+        // подпись раскрыла бы автора
+        ProfessionalNetworkingInstruction::WriteAnonymousPost {
+            content,
+            commitment,
+        } => {
+            let board_account = next_account_info(account_info_iter)?;
+            let mut board = load_anonymous_board(board_account, program_id)?;
+
+            let index = board.post(
+                *board_account.key,
+                content,
+                commitment,
+                current_timestamp()?,
+            )?;
+            board.serialize(&mut &mut board_account.try_borrow_mut_data()?[..])?;
+            msg!("AnonymousPostCreated: index={}", index);
+
+            Ok(())
+        }
+
+        ProfessionalNetworkingInstruction::CommentAnonymousPost {
+            post_index_on_board,
+            content,
+        } => {
+            let board_account = next_account_info(account_info_iter)?;
+            check_owner(user_account, program_id)?;
+            load_profile(user_account, &user_account.try_borrow_data()?)?.ensure_active()?;
+            let mut board = load_anonymous_board(board_account, program_id)?;
+
+            board.comment(post_index_on_board, *user_account.key, content)?;
+            board.serialize(&mut &mut board_account.try_borrow_mut_data()?[..])?;
+
+            Ok(())
+        }
+
+        // Подписывает автор: его ключ входит в commitment
+        ProfessionalNetworkingInstruction::RevealAnonymousPost {
+            post_index_on_board,
+            salt,
+        } => {
+            let board_account = next_account_info(account_info_iter)?;
+            let mut board = load_anonymous_board(board_account, program_id)?;
+
+            board.reveal(
+                post_index_on_board,
+                board_account.key,
+                *user_account.key,
+                &salt,
+            )?;
+            board.serialize(&mut &mut board_account.try_borrow_mut_data()?[..])?;
+            msg!(
+                "AnonymousPostRevealed: index={} author={}",
+                post_index_on_board,
+                user_account.key
+            );
 
             Ok(())
         }
     }
 }
+
+// Изменяемые заимствования аккаунтов в рамках одной инструкции. Один и тот же
+// аккаунт, переданный дважды, даёт DuplicateAccount, а занятый RefCell —
+// BorrowConflict вместо непрозрачного AccountBorrowFailed.
+#[derive(Default)]
+pub struct AccountBorrowGuard {
+    borrowed: Vec<Pubkey>,
+}
+
+impl AccountBorrowGuard {
+    pub fn borrow_mut_data<'b, 'a>(
+        &mut self,
+        account: &'b AccountInfo<'a>,
+    ) -> Result<RefMut<'b, &'a mut [u8]>, ProgramError> {
+        if self.borrowed.contains(account.key) {
+            msg!("Account {} passed twice", account.key);
+            return Err(NetworkingError::DuplicateAccount.into());
+        }
+        let data = account
+            .try_borrow_mut_data()
+            .map_err(|_| ProgramError::from(NetworkingError::BorrowConflict))?;
+        self.borrowed.push(*account.key);
+        Ok(data)
+    }
+}
+
+// Профили читаются только из аккаунтов этой программы: байты чужого аккаунта
+// могут случайно декодироваться как UserProfile
+fn check_owner(account: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+    if account.owner != program_id {
+        msg!("Account {} is owned by {}", account.key, account.owner);
+        return Err(ProgramError::IllegalOwner);
+    }
+    Ok(())
+}
+
+// Профиль из аккаунта, который действительно принадлежит его владельцу.
+// Архив SnapshotProfile хранит точную копию профиля и проходит check_owner,
+// поэтому без сверки адреса его можно было бы подставить вместо живого профиля.
+fn load_profile(account: &AccountInfo, data: &[u8]) -> Result<UserProfile, ProgramError> {
+    let profile = UserProfile::load(data)?;
+    if profile.address != *account.key {
+        msg!(
+            "Account {} holds the profile of {}",
+            account.key,
+            profile.address
+        );
+        return Err(NetworkingError::ProfileAddressMismatch.into());
+    }
+    Ok(profile)
+}
+
+fn require_signer(account: &AccountInfo) -> ProgramResult {
+    if !account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+// Загружает профиль владельца (он должен подписать), применяет изменение и сохраняет
+fn update_own_profile<F>(
+    user_account: &AccountInfo,
+    program_id: &Pubkey,
+    update: F,
+) -> ProgramResult
+where
+    F: FnOnce(&mut UserProfile) -> ProgramResult,
+{
+    update_own_setting(user_account, program_id, |profile| {
+        update(profile).map(|_| true)
+    })
+}
+
+// Как update_own_profile, но update сообщает, изменилось ли что-то:
+// без изменений профиль не сериализуется и аккаунт не перезаписывается
+fn update_own_setting<F>(
+    user_account: &AccountInfo,
+    program_id: &Pubkey,
+    update: F,
+) -> ProgramResult
+where
+    F: FnOnce(&mut UserProfile) -> Result<bool, ProgramError>,
+{
+    check_owner(user_account, program_id)?;
+    let mut user_data = user_account.try_borrow_mut_data()?;
+    let mut user_profile = load_profile(user_account, &user_data)?;
+
+    if !update(&mut user_profile)? {
+        msg!("NoChange");
+        return Ok(());
+    }
+    store_profile(&mut user_data, &user_profile)
+}
+
+// Записывает профиль в данные аккаунта; если он вышел за MAX_PROFILE_SIZE или
+// не помещается в аккаунт — ошибка вместо паники. Так ограничены и изменения,
+// для которых нет предварительной оценки ensure_room.
+fn store_profile(data: &mut [u8], profile: &UserProfile) -> ProgramResult {
+    let serialized_data = profile.try_to_vec()?;
+    if serialized_data.len() > MAX_PROFILE_SIZE {
+        msg!(
+            "ProfileSizeLimitReached: needed={} max={}",
+            serialized_data.len(),
+            MAX_PROFILE_SIZE
+        );
+        return Err(NetworkingError::ProfileSizeLimitReached.into());
+    }
+    if serialized_data.len() > data.len() {
+        msg!(
+            "AccountDataTooSmall: needed={} available={}",
+            serialized_data.len(),
+            data.len()
+        );
+        return Err(NetworkingError::AccountDataTooSmall.into());
+    }
+    data[..serialized_data.len()].copy_from_slice(&serialized_data);
+    Ok(())
+}
+
+// Читает конфигурацию, проверяя адрес PDA и владельца аккаунта
+fn load_config(
+    config_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<NetworkConfig, ProgramError> {
+    let (config_key, _) = config_address(program_id);
+    if config_account.key != &config_key || config_account.owner != program_id {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let config = NetworkConfig::deserialize(&mut &config_account.try_borrow_data()?[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !config.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(config)
+}
+
+// Upgrade authority программы из её аккаунта ProgramData; None — программа неизменяемая
+fn upgrade_authority(
+    program_data: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<Option<Pubkey>, ProgramError> {
+    let (program_data_key, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if program_data.key != &program_data_key || program_data.owner != &bpf_loader_upgradeable::id()
+    {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    // ProgramData: тег варианта (u32 = 3), слот (u64), Option<Pubkey>
+    let data = program_data.try_borrow_data()?;
+    if data.len() < UpgradeableLoaderState::size_of_programdata_metadata()
+        || data[..4] != 3u32.to_le_bytes()
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    match data[12] {
+        0 => Ok(None),
+        1 => Pubkey::try_from(&data[13..45])
+            .map(Some)
+            .map_err(|_| ProgramError::InvalidAccountData),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// Читает отметку индексатора, проверяя владельца и адрес PDA
+fn load_indexer_checkpoint(
+    checkpoint_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<IndexerCheckpoint, ProgramError> {
+    check_owner(checkpoint_account, program_id)?;
+    let checkpoint =
+        IndexerCheckpoint::deserialize(&mut &checkpoint_account.try_borrow_data()?[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !checkpoint.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if checkpoint_account.key != &indexer_checkpoint_address(program_id, &checkpoint.indexer).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(checkpoint)
+}
+
+// Читает доску анонимных вопросов, проверяя владельца и адрес PDA
+fn load_anonymous_board(
+    board_account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<AnonymousBoard, ProgramError> {
+    check_owner(board_account, program_id)?;
+    if board_account.key != &anonymous_board_address(program_id).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let board = AnonymousBoard::deserialize(&mut &board_account.try_borrow_data()?[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !board.is_initialized {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(board)
+}
+
+// Сколько из переданных профилей — подтверждённые друзья profile; каждый аккаунт учитывается один раз
+fn count_confirmed_friends(
+    profile: &UserProfile,
+    friend_accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> Result<usize, ProgramError> {
+    let mut seen = HashSet::new();
+    let mut confirmed = 0;
+    for friend_account in friend_accounts {
+        if friend_account.key == &profile.address || !seen.insert(*friend_account.key) {
+            return Err(NetworkingError::DuplicateAccount.into());
+        }
+        check_owner(friend_account, program_id)?;
+        let friend = load_profile(friend_account, &friend_account.try_borrow_data()?)?;
+        if profile.is_confirmed_friend(&friend) {
+            confirmed += 1;
+        }
+    }
+    Ok(confirmed)
+}
+
+// Создаёт PDA, принадлежащий программе, за счёт payer
+fn create_program_account<'a>(
+    payer: &'a AccountInfo<'a>,
+    new_account: &'a AccountInfo<'a>,
+    system_program: &'a AccountInfo<'a>,
+    program_id: &Pubkey,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    require_signer(payer)?;
+    let rent = Rent::get()?;
+    let create_ix = solana_program::system_instruction::create_account(
+        payer.key,
+        new_account.key,
+        rent.minimum_balance(space),
+        space as u64,
+        program_id,
+    );
+    invoke_signed(
+        &create_ix,
+        &[payer.clone(), new_account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+// Аренду и комиссии за новые аккаунты оплачивает payer, а не сам пользователь,
+// чтобы спонсор мог подключать кошельки без SOL
 fn create_nft<'a>(
     nft_mint_account: &'a AccountInfo<'a>,
     nft_account: &'a AccountInfo<'a>,
     user_account: &'a AccountInfo<'a>,
+    payer: &'a AccountInfo<'a>,
     system_program: &'a AccountInfo<'a>,
     token_program: &'a AccountInfo<'a>,
     rent_sysvar: &'a AccountInfo<'a>,
 ) -> ProgramResult {
+    require_signer(payer)?;
     let rent = Rent::from_account_info(rent_sysvar)?;
     let nft_mint_key = nft_mint_account.key;
     let user_key = user_account.key;
@@ -265,7 +3141,7 @@ fn create_nft<'a>(
 
     // Create the mint account
     let mint_ix = solana_program::system_instruction::create_account(
-        user_key,
+        payer.key,
         nft_mint_key,
         rent.minimum_balance(82),
         82,
@@ -274,7 +3150,7 @@ fn create_nft<'a>(
     invoke_signed(
         &mint_ix,
         &[
-            user_account.clone(),
+            payer.clone(),
             nft_mint_account.clone(),
             system_program.clone(),
         ],
@@ -296,7 +3172,7 @@ fn create_nft<'a>(
 
     // Create the token account for the user
     let create_token_account_ix = solana_program::system_instruction::create_account(
-        user_key,
+        payer.key,
         nft_account.key,
         rent.minimum_balance(165),
         165,
@@ -304,11 +3180,7 @@ fn create_nft<'a>(
     );
     invoke_signed(
         &create_token_account_ix,
-        &[
-            user_account.clone(),
-            nft_account.clone(),
-            system_program.clone(),
-        ],
+        &[payer.clone(), nft_account.clone(), system_program.clone()],
         &[signers_seeds],
     )?;
 
@@ -1,196 +1,2521 @@
-#[cfg(test)]
 mod tests {
-    use super::*;
-    use solana_program_test::*;
-    use solana_sdk::signature::Keypair;
+    use crate::*;
 
-    #[tokio::test]
-    async fn test_create_user_profile() {
+    fn identity(platform: u8, oracle: Pubkey, hash_byte: u8) -> ExternalIdentity {
+        ExternalIdentity {
+            platform,
+            handle_hash: [hash_byte; 32],
+            attested_by: oracle,
+            attested_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_attest_external_identity() {
+        let oracle = Pubkey::new_unique();
+        let config = NetworkConfig::new(Pubkey::new_unique(), vec![oracle]);
+        let mut user_profile = UserProfile::new(
+            "Alice".to_string(),
+            "Bio of Alice".to_string(),
+            "url-to-picture".to_string(),
+            Pubkey::new_unique(),
+        );
+
+        assert!(config.is_oracle(&oracle));
+        user_profile.attest_external_identity(identity(1, oracle, 7)).unwrap();
+
+        assert_eq!(user_profile.external_identities.len(), 1);
+        assert_eq!(user_profile.external_identities[0].attested_by, oracle);
+    }
+
+    // Данные аккаунта конфигурации в том виде, в каком их пишет InitializeConfig
+    fn config_account_data(config: &NetworkConfig) -> Vec<u8> {
+        let mut data = config.try_to_vec().unwrap();
+        data.resize(NetworkConfig::LEN, 0);
+        data
+    }
+
+    // Заглушки системных вызовов для process_instruction вне рантайма: Clock с
+    // временем потока (None — sysvar недоступен), Rent по умолчанию и перехват msg!
+    // в лог текущего потока
+    const TEST_NOW: i64 = 1_700_000_000;
+
+    thread_local! {
+        static LOGS: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+        static CLOCK: std::cell::Cell<Option<i64>> = const { std::cell::Cell::new(Some(TEST_NOW)) };
+    }
+
+    struct TestSyscalls;
+
+    impl solana_program::program_stubs::SyscallStubs for TestSyscalls {
+        fn sol_log(&self, message: &str) {
+            LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+        }
+
+        fn sol_invoke_signed(&self, _: &solana_program::instruction::Instruction, _: &[AccountInfo], _: &[&[&[u8]]]) -> ProgramResult {
+            Ok(())
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let Some(unix_timestamp) = CLOCK.with(|clock| clock.get()) else {
+                return solana_program::program_error::UNSUPPORTED_SYSVAR;
+            };
+            let clock = Clock { unix_timestamp, ..Clock::default() };
+            unsafe { (var_addr as *mut Clock).write(clock) };
+            solana_program::entrypoint::SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { (var_addr as *mut Rent).write(Rent::default()) };
+            solana_program::entrypoint::SUCCESS
+        }
+    }
+
+    fn install_syscalls() {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            solana_program::program_stubs::set_syscall_stubs(Box::new(TestSyscalls));
+        });
+    }
+
+    // Запускает инструкцию с заглушками и возвращает результат вместе с её логом
+    fn run_logged<'a>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'a>],
+        instruction: &ProfessionalNetworkingInstruction,
+    ) -> (ProgramResult, Vec<String>) {
+        install_syscalls();
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        let result = process_instruction(program_id, accounts, &instruction.try_to_vec().unwrap());
+        (result, LOGS.with(|logs| logs.take()))
+    }
+
+    fn run<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], instruction: &ProfessionalNetworkingInstruction) -> ProgramResult {
+        run_logged(program_id, accounts, instruction).0
+    }
+
+    // Профиль, которому уже можно писать посты и комментировать
+    fn member_profile(address: Pubkey) -> UserProfile {
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut profile = profile_with_friends(address, &friends);
+        profile.grant_membership(TEST_NOW);
+        profile
+    }
+
+    // Данные аккаунта профиля с запасом места под рост
+    fn profile_account_data(profile: &UserProfile) -> Vec<u8> {
+        let mut data = profile.try_to_vec().unwrap();
+        data.resize(data.len() + 1024, 0);
+        data
+    }
+
+    #[test]
+    fn test_create_user_profile() {
         let program_id = Pubkey::new_unique();
-        let mut test = ProgramTest::new("professional_networking", program_id, processor!(process_instruction));
-        let user_account = Keypair::new();
+        let user = Pubkey::new_unique();
+        let (mut lamports, mut data) = (0, vec![0; 512]);
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
 
-        test.add_account(user_account.pubkey(), Account::new(0, 0, &program_id));
-        let (mut banks_client, payer, recent_blockhash) = test.start().await;
-        let instruction_data = ProfessionalNetworkingInstruction::CreateUserProfile {
+        let instruction = ProfessionalNetworkingInstruction::CreateUserProfile {
             name: "Alice".to_string(),
             bio: "Bio of Alice".to_string(),
             profile_picture: "url-to-picture".to_string(),
+            referred_by: None,
+        };
+        assert_eq!(run(&program_id, &accounts, &instruction), Ok(()));
+
+        let user_profile = UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap();
+        assert_eq!(user_profile.name, "Alice");
+        assert_eq!(user_profile.bio, "Bio of Alice");
+        assert_eq!(user_profile.profile_picture, "url-to-picture");
+        assert_eq!(user_profile.address, user);
+    }
+
+    #[test]
+    fn test_send_friend_request() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = profile_account_data(&profile_with_friends(user, &[]));
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let instruction = ProfessionalNetworkingInstruction::SendFriendRequest { friend_address: friend };
+        assert_eq!(run(&program_id, &accounts, &instruction), Ok(()));
+
+        let user_profile = UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap();
+        assert!(user_profile.friends.contains(&friend));
+        assert_eq!(user_profile.requests_sent.get(), 1);
+    }
+
+    #[test]
+    fn test_accept_friend_request() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let (mut user_lamports, mut friend_lamports) = (0, 0);
+        let mut user_data = profile_account_data(&profile_with_friends(user, &[]));
+        let mut friend_data = profile_account_data(&profile_with_friends(friend, &[]));
+        let user_info = AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &program_id, false, 0);
+        let friend_info = AccountInfo::new(&friend, true, true, &mut friend_lamports, &mut friend_data, &program_id, false, 0);
+        let by_user = [user_info.clone()];
+        let by_friend = [friend_info.clone(), user_info.clone()];
+
+        let send = ProfessionalNetworkingInstruction::SendFriendRequest { friend_address: friend };
+        assert_eq!(run(&program_id, &by_user, &send), Ok(()));
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: user };
+        assert_eq!(run(&program_id, &by_friend, &accept), Ok(()));
+
+        let user_profile = UserProfile::load(&user_info.try_borrow_data().unwrap()).unwrap();
+        let friend_profile = UserProfile::load(&friend_info.try_borrow_data().unwrap()).unwrap();
+        assert!(user_profile.friends.contains(&friend));
+        assert!(friend_profile.friends.contains(&user));
+        assert_eq!(user_profile.requests_accepted.get(), 1);
+    }
+
+    #[test]
+    fn test_write_post() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = profile_account_data(&member_profile(user));
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let content = "Hello World!".to_string();
+        let instruction = ProfessionalNetworkingInstruction::WritePost { content: content.clone(), tags: vec![], attachments: vec![] };
+        assert_eq!(run(&program_id, &accounts, &instruction), Ok(()));
+
+        let user_profile = UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap();
+        assert_eq!(user_profile.posts.len(), 1);
+        assert_eq!(user_profile.posts.get(&user).unwrap().len(), 1);
+        assert_eq!(user_profile.posts.get(&user).unwrap()[0].content, content);
+        assert_eq!(user_profile.posts.get(&user).unwrap()[0].timestamp, TEST_NOW);
+    }
+
+    #[test]
+    fn test_add_comment() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = profile_account_data(&member_profile(user));
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let write_post = ProfessionalNetworkingInstruction::WritePost { content: "Hello World!".to_string(), tags: vec![], attachments: vec![] };
+        assert_eq!(run(&program_id, &accounts, &write_post), Ok(()));
+        let comment_content = "Nice post!".to_string();
+        let add_comment = ProfessionalNetworkingInstruction::AddComment { post_author: user, post_index: 0, content: comment_content.clone() };
+        assert_eq!(run(&program_id, &accounts, &add_comment), Ok(()));
+
+        let user_profile = UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap();
+        let post_with_comments = user_profile.get_post_with_comments(&user, 0).unwrap();
+        assert_eq!(post_with_comments.comments.len(), 1);
+        assert_eq!(post_with_comments.comments[0].content, comment_content);
+    }
+
+    #[test]
+    fn test_non_oracle_cannot_attest() {
+        let program_id = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let config = NetworkConfig::new(Pubkey::new_unique(), vec![oracle]);
+        assert!(!config.is_oracle(&config.admin));
+
+        for signer in [Pubkey::new_unique(), config.admin] {
+            let config_key = config_address(&program_id).0;
+            let profile_key = Pubkey::new_unique();
+            let (mut signer_lamports, mut config_lamports, mut profile_lamports) = (0, 0, 0);
+            let mut signer_data = vec![];
+            let mut config_data = config_account_data(&config);
+            let mut profile_data = profile_with_friends(profile_key, &[]).try_to_vec().unwrap();
+            let before = profile_data.clone();
+            let accounts = [
+                AccountInfo::new(&signer, true, false, &mut signer_lamports, &mut signer_data, &program_id, false, 0),
+                AccountInfo::new(&config_key, false, false, &mut config_lamports, &mut config_data, &program_id, false, 0),
+                AccountInfo::new(&profile_key, false, true, &mut profile_lamports, &mut profile_data, &program_id, false, 0),
+            ];
+            let instruction = ProfessionalNetworkingInstruction::AttestExternalIdentity { platform: 1, handle_hash: [7; 32] };
+            assert_eq!(
+                process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()),
+                Err(NetworkingError::Unauthorized.into())
+            );
+            assert_eq!(*accounts[2].try_borrow_data().unwrap(), &before[..]);
         }
-        .try_to_vec()
-        .unwrap();
+    }
+
+    #[test]
+    fn test_initialize_config_requires_upgrade_authority() {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let loader = bpf_loader_upgradeable::id();
+        let program_data_key = Pubkey::find_program_address(&[program_id.as_ref()], &loader).0;
+        let program_data = |authority: Option<Pubkey>| {
+            // UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address }
+            let mut data = 3u32.to_le_bytes().to_vec();
+            data.extend(0u64.to_le_bytes());
+            match authority {
+                Some(key) => {
+                    data.push(1);
+                    data.extend(key.to_bytes());
+                }
+                None => data.extend([0; 33]),
+            }
+            data
+        };
 
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &instruction_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
+        let cases = [
+            (Pubkey::new_unique(), program_data_key, program_data(Some(authority)), Err(NetworkingError::Unauthorized.into())),
+            (authority, program_data_key, program_data(None), Err(NetworkingError::Unauthorized.into())),
+            (authority, Pubkey::new_unique(), program_data(Some(authority)), Err(ProgramError::InvalidSeeds)),
+        ];
+        for (signer, data_key, mut data, expected) in cases {
+            let config_key = config_address(&program_id).0;
+            let system_program = solana_program::system_program::id();
+            let (mut signer_lamports, mut config_lamports, mut system_lamports, mut data_lamports) = (0, 0, 0, 0);
+            let (mut signer_data, mut config_data, mut system_data) = (vec![], vec![], vec![]);
+            let signer_info = AccountInfo::new(&signer, true, true, &mut signer_lamports, &mut signer_data, &program_id, false, 0);
+            // Подписант платит сам за себя
+            let accounts = [
+                signer_info.clone(),
+                AccountInfo::new(&config_key, false, true, &mut config_lamports, &mut config_data, &program_id, false, 0),
+                signer_info,
+                AccountInfo::new(&system_program, false, false, &mut system_lamports, &mut system_data, &system_program, true, 0),
+                AccountInfo::new(&data_key, false, false, &mut data_lamports, &mut data, &loader, false, 0),
+            ];
+            let instruction = ProfessionalNetworkingInstruction::InitializeConfig { oracles: vec![] };
+            assert_eq!(process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()), expected);
+            assert!(accounts[1].data_is_empty());
+        }
+    }
+
+    #[test]
+    fn test_attest_external_identity_replaces_platform_entry() {
+        let oracle = Pubkey::new_unique();
+        let mut user_profile = UserProfile::new(
+            "Alice".to_string(),
+            "Bio of Alice".to_string(),
+            "url-to-picture".to_string(),
+            Pubkey::new_unique(),
         );
-        transaction.sign(&[&payer], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
 
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
+        user_profile.attest_external_identity(identity(1, oracle, 7)).unwrap();
+        user_profile.attest_external_identity(identity(1, oracle, 9)).unwrap();
 
-        assert_eq!(user_profile.name, "Alice");
-        assert_eq!(user_profile.bio, "Bio of Alice");
-        assert_eq!(user_profile.profile_picture, "url-to-picture");
+        assert_eq!(user_profile.external_identities.len(), 1);
+        assert_eq!(user_profile.external_identities[0].handle_hash, [9; 32]);
+
+        for platform in 2..=MAX_EXTERNAL_IDENTITIES as u8 {
+            user_profile.attest_external_identity(identity(platform, oracle, 1)).unwrap();
+        }
+        assert_eq!(
+            user_profile.attest_external_identity(identity(42, oracle, 1)),
+            Err(NetworkingError::IdentityLimitReached.into())
+        );
+
+        user_profile.remove_external_identity(1).unwrap();
+        assert_eq!(
+            user_profile.remove_external_identity(1),
+            Err(NetworkingError::IdentityNotFound.into())
+        );
+    }
+
+    fn profile_with_friends(address: Pubkey, friends: &[Pubkey]) -> UserProfile {
+        let mut profile = UserProfile::new(String::new(), String::new(), String::new(), address);
+        profile.friends.extend(friends.iter().copied());
+        profile
+    }
+
+    #[test]
+    fn test_friends_of_friends() {
+        let me = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let carol = Pubkey::new_unique();
+        let dave = Pubkey::new_unique();
+        let erin = Pubkey::new_unique();
+
+        let my_profile = profile_with_friends(me, &[bob, carol]);
+        let mut friend_profiles = HashMap::new();
+        friend_profiles.insert(bob, profile_with_friends(bob, &[me, carol, dave]));
+        friend_profiles.insert(carol, profile_with_friends(carol, &[me, bob, dave, erin]));
+
+        let suggestions = friends_of_friends(&my_profile, &friend_profiles);
+
+        assert_eq!(suggestions, [dave, erin].into_iter().collect::<HashSet<_>>());
+        assert!(!suggestions.contains(&me));
+        assert!(!suggestions.contains(&bob));
+        assert!(!suggestions.contains(&carol));
+    }
+
+    #[test]
+    fn test_graph_density() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let c = Pubkey::new_unique();
+        let profile_a = profile_with_friends(a, &[b, c]);
+        let profile_b = profile_with_friends(b, &[a, c]);
+        let profile_c = profile_with_friends(c, &[a, b]);
+
+        assert_eq!(graph_density(&[&profile_a, &profile_b, &profile_c]), 1.0);
+        assert_eq!(graph_density(&[&profile_a]), 0.0);
+
+        let profile_d = profile_with_friends(Pubkey::new_unique(), &[]);
+        assert_eq!(graph_density(&[&profile_a, &profile_b, &profile_c, &profile_d]), 0.5);
+    }
+
+    #[test]
+    fn test_claim_referral_once() {
+        let referrer_key = Pubkey::new_unique();
+        let mut referrer = profile_with_friends(referrer_key, &[]);
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut referred = profile_with_friends(Pubkey::new_unique(), &friends);
+        referred.referred_by = Some(referrer_key);
+
+        assert_eq!(
+            referred.claim_referral(&mut referrer),
+            Err(NetworkingError::ReferralNotEligible.into())
+        );
+
+        referred.nft_owned = true;
+        referred.claim_referral(&mut referrer).unwrap();
+        assert_eq!(
+            referred.claim_referral(&mut referrer),
+            Err(NetworkingError::ReferralAlreadyClaimed.into())
+        );
+
+        assert_eq!(referrer.referral_count.get(), 1);
+        assert!(referred.referral_claimed);
     }
 
-    #[tokio::test]
-    async fn test_send_friend_request() {
+    #[test]
+    fn test_create_user_profile_rejects_initialized_account() {
         let program_id = Pubkey::new_unique();
-        let mut test = ProgramTest::new("professional_networking", program_id, processor!(process_instruction));
-        let user_account = Keypair::new();
-        let friend_account = Keypair::new();
-        test.add_account(user_account.pubkey(), Account::new(0, 0, &program_id));
-        test.add_account(friend_account.pubkey(), Account::new(0, 0, &program_id));
+        let user = Pubkey::new_unique();
+        let mut profile = profile_with_friends(user, &[]);
+        profile.referred_by = Some(Pubkey::new_unique());
+        profile.referral_claimed = true;
+        let mut lamports = 0;
+        let mut data = profile.try_to_vec().unwrap();
+        let before = data.clone();
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let instruction = ProfessionalNetworkingInstruction::CreateUserProfile {
+            name: "Alice".to_string(),
+            bio: String::new(),
+            profile_picture: String::new(),
+            referred_by: profile.referred_by,
+        };
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        assert_eq!(*accounts[0].try_borrow_data().unwrap(), &before[..]);
+    }
 
-        let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    #[test]
+    fn test_self_referral_rejected() {
+        let key = Pubkey::new_unique();
+        let mut profile = profile_with_friends(key, &[]);
+        profile.referred_by = Some(key);
+        profile.nft_owned = true;
+        let mut same_profile = profile.clone();
+
+        assert_eq!(
+            profile.claim_referral(&mut same_profile),
+            Err(NetworkingError::SelfReferral.into())
+        );
+        assert_eq!(same_profile.referral_count.get(), 0);
+    }
 
-        let instruction_data = ProfessionalNetworkingInstruction::SendFriendRequest {
-            friend_address: friend_account.pubkey(),
+    #[test]
+    fn test_error_table_covers_every_variant() {
+        for (code, message) in ERROR_TABLE {
+            let error = NetworkingError::from_code(*code).unwrap();
+            assert_eq!(error.code(), *code);
+            assert_eq!(error.message(), *message);
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(*code));
+            match error {
+                NetworkingError::AlreadyFriends
+                | NetworkingError::NotEligible
+                | NetworkingError::PostNotFound
+                | NetworkingError::Unauthorized
+                | NetworkingError::TooManyOracles
+                | NetworkingError::IdentityLimitReached
+                | NetworkingError::IdentityNotFound
+                | NetworkingError::SelfReferral
+                | NetworkingError::ReferrerMismatch
+                | NetworkingError::ReferralAlreadyClaimed
+                | NetworkingError::ReferralNotEligible
+                | NetworkingError::TooManyTags
+                | NetworkingError::CommentLimitReached
+                | NetworkingError::ClockUnavailable
+                | NetworkingError::InvalidTheme
+                | NetworkingError::CommentNotFound
+                | NetworkingError::ProfileDeactivated
+                | NetworkingError::SelfReport
+                | NetworkingError::SectionLimitReached
+                | NetworkingError::SectionNotFound
+                | NetworkingError::SectionTooLong
+                | NetworkingError::InvalidSectionOrder
+                | NetworkingError::InvalidTag
+                | NetworkingError::InvalidDomain
+                | NetworkingError::ProfileNotInitialized
+                | NetworkingError::AccountTooLarge
+                | NetworkingError::InvalidRetentionPolicy
+                | NetworkingError::RepairsDisabled
+                | NetworkingError::TooManyAttachments
+                | NetworkingError::InvalidAttachmentUri
+                | NetworkingError::AttachmentsImmutable
+                | NetworkingError::ContactBlobTooLarge
+                | NetworkingError::NotFriends
+                | NetworkingError::InvalidTimezone
+                | NetworkingError::DuplicateAccount
+                | NetworkingError::BorrowConflict
+                | NetworkingError::AccountDataTooSmall
+                | NetworkingError::ProfileSizeLimitReached
+                | NetworkingError::InvalidAvailability
+                | NetworkingError::DuplicateAttachmentUri
+                | NetworkingError::ProfileAddressMismatch
+                | NetworkingError::CheckpointRegression
+                | NetworkingError::RevealMismatch
+                | NetworkingError::AlreadyRevealed
+                | NetworkingError::AnonymousTextTooLong => {}
+            }
         }
-        .try_to_vec()
-        .unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &instruction_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
+        assert_eq!(ERROR_TABLE.len(), 45);
+        assert_eq!(NetworkingError::from_code(ERROR_TABLE.len() as u32), None);
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        assert_eq!(NetworkingError::AlreadyFriends.code(), 0);
+        assert_eq!(NetworkingError::NotEligible.code(), 1);
+        assert_eq!(NetworkingError::PostNotFound.code(), 2);
+        assert_eq!(NetworkingError::Unauthorized.code(), 3);
+        assert_eq!(NetworkingError::TooManyOracles.code(), 4);
+        assert_eq!(NetworkingError::IdentityLimitReached.code(), 5);
+        assert_eq!(NetworkingError::IdentityNotFound.code(), 6);
+        assert_eq!(NetworkingError::SelfReferral.code(), 7);
+        assert_eq!(NetworkingError::ReferrerMismatch.code(), 8);
+        assert_eq!(NetworkingError::ReferralAlreadyClaimed.code(), 9);
+        assert_eq!(NetworkingError::ReferralNotEligible.code(), 10);
+        assert_eq!(NetworkingError::TooManyTags.code(), 11);
+        assert_eq!(NetworkingError::CommentLimitReached.code(), 12);
+        assert_eq!(NetworkingError::ClockUnavailable.code(), 13);
+        assert_eq!(NetworkingError::InvalidTheme.code(), 14);
+        assert_eq!(NetworkingError::CommentNotFound.code(), 15);
+        assert_eq!(NetworkingError::ProfileDeactivated.code(), 16);
+        assert_eq!(NetworkingError::SelfReport.code(), 17);
+        assert_eq!(NetworkingError::SectionLimitReached.code(), 18);
+        assert_eq!(NetworkingError::SectionNotFound.code(), 19);
+        assert_eq!(NetworkingError::SectionTooLong.code(), 20);
+        assert_eq!(NetworkingError::InvalidSectionOrder.code(), 21);
+        assert_eq!(NetworkingError::InvalidTag.code(), 22);
+        assert_eq!(NetworkingError::InvalidDomain.code(), 23);
+        assert_eq!(NetworkingError::ProfileNotInitialized.code(), 24);
+        assert_eq!(NetworkingError::AccountTooLarge.code(), 25);
+        assert_eq!(NetworkingError::InvalidRetentionPolicy.code(), 26);
+        assert_eq!(NetworkingError::RepairsDisabled.code(), 27);
+        assert_eq!(NetworkingError::TooManyAttachments.code(), 28);
+        assert_eq!(NetworkingError::InvalidAttachmentUri.code(), 29);
+        assert_eq!(NetworkingError::AttachmentsImmutable.code(), 30);
+        assert_eq!(NetworkingError::ContactBlobTooLarge.code(), 31);
+        assert_eq!(NetworkingError::NotFriends.code(), 32);
+        assert_eq!(NetworkingError::InvalidTimezone.code(), 33);
+        assert_eq!(NetworkingError::DuplicateAccount.code(), 34);
+        assert_eq!(NetworkingError::BorrowConflict.code(), 35);
+        assert_eq!(NetworkingError::AccountDataTooSmall.code(), 36);
+        assert_eq!(NetworkingError::ProfileSizeLimitReached.code(), 37);
+        assert_eq!(NetworkingError::InvalidAvailability.code(), 38);
+        assert_eq!(NetworkingError::DuplicateAttachmentUri.code(), 39);
+        assert_eq!(NetworkingError::ProfileAddressMismatch.code(), 40);
+        assert_eq!(NetworkingError::CheckpointRegression.code(), 41);
+        assert_eq!(NetworkingError::RevealMismatch.code(), 42);
+        assert_eq!(NetworkingError::AlreadyRevealed.code(), 43);
+        assert_eq!(NetworkingError::AnonymousTextTooLong.code(), 44);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_build_instructions_rejects_post_before_eligibility() {
+        use crate::client::{build_instructions, Action, NftAccounts};
+
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut existing = HashMap::new();
+        for friend in &friends {
+            existing.insert(*friend, profile_with_friends(*friend, &[]));
+        }
+
+        let create = Action::CreateUserProfile {
+            user,
+            name: "Alice".to_string(),
+            bio: "Bio of Alice".to_string(),
+            profile_picture: "url-to-picture".to_string(),
+            referred_by: None,
+        };
+        let post = Action::WritePost {
+            user,
+            content: "Hello World!".to_string(),
+            tags: vec![],
+            attachments: vec![],
+        };
+
+        let payer = Pubkey::new_unique();
+        let error =
+            build_instructions(&program_id, &payer, &existing, &[create.clone(), post.clone()]).unwrap_err();
+        assert_eq!(error.index, 1);
+        assert_eq!(error.error, NetworkingError::NotEligible.into());
+
+        let mut actions = vec![create];
+        for friend in &friends {
+            actions.push(Action::AcceptFriendRequest {
+                user,
+                friend: *friend,
+                nft: Some(NftAccounts {
+                    mint: Pubkey::new_unique(),
+                    token_account: Pubkey::new_unique(),
+                }),
+            });
+        }
+        actions.push(post);
+        let instructions = build_instructions(&program_id, &payer, &existing, &actions).unwrap();
+
+        assert_eq!(instructions.len(), NFT_FRIEND_THRESHOLD + 2);
+        assert_eq!(instructions[1].accounts.len(), 2);
+        assert_eq!(instructions[NFT_FRIEND_THRESHOLD].accounts.len(), 8);
+        assert!(instructions.iter().all(|ix| ix.accounts[0].is_signer));
+    }
+
+    #[test]
+    fn test_posts_with_tag() {
+        let author = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(author, &[]);
+        let tags = |list: &[&str]| normalize_tags(list.iter().map(|t| t.to_string()).collect()).unwrap();
+
+        user_profile.add_post(author, "First".to_string(), tags(&["Rust", "solana"]), vec![], 0);
+        user_profile.add_post(author, "Second".to_string(), tags(&[" rust ", "hiring", "RUST"]), vec![], 0);
+        user_profile.add_post(author, "Third".to_string(), tags(&["hiring"]), vec![], 0);
+
+        let rust_posts: Vec<&str> = user_profile
+            .posts_with_tag(&author, "Rust")
+            .iter()
+            .map(|post| post.content.as_str())
+            .collect();
+        assert_eq!(rust_posts, vec!["First", "Second"]);
+        assert_eq!(user_profile.posts_with_tag(&author, "hiring").len(), 2);
+        assert_eq!(user_profile.posts_with_tag(&author, "go").len(), 0);
+        assert_eq!(user_profile.posts[&author][1].tags, vec!["rust", "hiring"]);
+
+        let too_many = (0..=MAX_POST_TAGS).map(|i| format!("tag{}", i)).collect();
+        assert_eq!(normalize_tags(too_many), Err(NetworkingError::TooManyTags.into()));
+    }
+
+    #[test]
+    fn test_counters_saturate_instead_of_wrapping() {
+        fn check<T: Copy + SaturatingAdd + One + PartialOrd + std::fmt::Debug>(max: T, below: &[T]) {
+            for start in below.iter().copied().chain([max]) {
+                let mut counter = Counter::new(start);
+                let mut previous = counter.get();
+                for _ in 0..4 {
+                    counter.increment();
+                    assert!(counter.get() >= previous);
+                    previous = counter.get();
+                }
+            }
+            let mut counter = Counter::new(max);
+            counter.increment();
+            assert_eq!(counter.get(), max);
+        }
+        check(u8::MAX, &[0, 1, u8::MAX - 3, u8::MAX - 1]);
+        check(u16::MAX, &[0, u16::MAX - 2]);
+        check(u32::MAX, &[0, u32::MAX - 2]);
+        check(u64::MAX, &[0, u64::MAX - 1]);
+
+        // Счётчики профиля на максимуме: операции проходят, значение не заворачивается
+        let viewer = Pubkey::new_unique();
+        let mut post = Post::new(Pubkey::new_unique(), "post".to_string(), vec![], 0);
+        post.impressions = Counter::new(u32::MAX);
+        assert_eq!(post.record_impression(&viewer, 0), Ok(true));
+        assert_eq!(post.impressions.get(), u32::MAX);
+    }
+
+    #[test]
+    fn test_counter_layout_matches_plain_integers() {
+        assert_eq!(Counter::new(7u32).try_to_vec().unwrap(), 7u32.to_le_bytes());
+
+        // Комментарий, записанный до Counter, с quote_count: u32
+        let author = Pubkey::new_unique();
+        let old_comment = (author, "hello".to_string(), 3u32).try_to_vec().unwrap();
+        let comment = Comment::try_from_slice(&old_comment).unwrap();
+        assert_eq!((comment.author, comment.quote_count.get()), (author, 3));
+        assert_eq!(comment.try_to_vec().unwrap(), old_comment);
+
+        // Счётчики профиля лежат на тех же местах, что и прежние u32
+        let mut profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        profile.send_friend_request(Pubkey::new_unique()).unwrap();
+        let data = profile.try_to_vec().unwrap();
+        let tail = (Counter::new(1u32), Counter::new(0u32), profile.available_hours).try_to_vec().unwrap();
+        let plain_tail = (1u32, 0u32, profile.available_hours).try_to_vec().unwrap();
+        assert_eq!(tail, plain_tail);
+        assert!(data.ends_with(&plain_tail));
+    }
+
+    #[test]
+    fn test_build_adjacency() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let c = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
+        let profile_a = profile_with_friends(a, &[b, c, outsider]);
+        let profile_b = profile_with_friends(b, &[a]);
+        let profile_c = profile_with_friends(c, &[b]);
+
+        let adjacency = build_adjacency(&[&profile_a, &profile_b, &profile_c]);
+
+        assert_eq!(adjacency.len(), 3);
+        assert_eq!(adjacency[&a], [b, c].into_iter().collect::<HashSet<_>>());
+        assert_eq!(adjacency[&b], [a, c].into_iter().collect::<HashSet<_>>());
+        assert_eq!(adjacency[&c], [a, b].into_iter().collect::<HashSet<_>>());
+        assert!(!adjacency.contains_key(&outsider));
+    }
+
+    #[test]
+    fn test_comment_capacity_used() {
+        let author = Pubkey::new_unique();
+        let mut post = Post::new(author, "Hello World!".to_string(), vec![], 0);
+        assert_eq!(post.comment_capacity_used(), 0.0);
+
+        for _ in 0..MAX_COMMENTS_PER_POST / 2 {
+            post.add_comment(author, "Nice post!".to_string()).unwrap();
+        }
+        assert_eq!(post.comment_capacity_used(), 0.5);
+
+        for _ in MAX_COMMENTS_PER_POST / 2..MAX_COMMENTS_PER_POST {
+            post.add_comment(author, "Nice post!".to_string()).unwrap();
+        }
+        assert_eq!(post.comment_capacity_used(), 1.0);
+        assert_eq!(
+            post.add_comment(author, "One more".to_string()),
+            Err(NetworkingError::CommentLimitReached.into())
         );
-        transaction.sign(&[&payer, &user_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    #[test]
+    fn test_record_impression() {
+        let author = Pubkey::new_unique();
+        let viewer = Pubkey::new_unique();
+        let mut post = Post::new(author, "Hello World!".to_string(), vec![], 0);
 
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
+        assert!(post.record_impression(&viewer, 0).unwrap());
+        assert_eq!(post.impressions.get(), 1);
 
-        assert!(user_profile.friends.contains(&friend_account.pubkey()));
+        assert!(!post.record_impression(&viewer, 0).unwrap());
+        assert_eq!(post.impressions.get(), 1);
+
+        assert!(!post.record_impression(&author, 0).unwrap());
+        assert_eq!(post.impressions.get(), 1);
+
+        assert!(post.record_impression(&Pubkey::new_unique(), 0).unwrap());
+        assert_eq!(post.impressions.get(), 2);
+
+        for _ in 0..IMPRESSION_DEDUP_WINDOW {
+            post.record_impression(&Pubkey::new_unique(), 0).unwrap();
+        }
+        assert_eq!(post.recent_viewers.len(), IMPRESSION_DEDUP_WINDOW);
+        assert!(post.record_impression(&viewer, 0).unwrap());
     }
 
-    #[tokio::test]
-    async fn test_accept_friend_request() {
+    #[test]
+    fn test_clock_unavailable() {
+        install_syscalls();
+        CLOCK.with(|clock| clock.set(None));
+        assert_eq!(current_timestamp(), Err(NetworkingError::ClockUnavailable.into()));
+        CLOCK.with(|clock| clock.set(Some(TEST_NOW)));
+        assert_eq!(current_timestamp(), Ok(TEST_NOW));
+    }
+
+    #[test]
+    fn test_set_theme() {
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        assert_eq!(user_profile.theme, Theme::Default as u8);
+
+        user_profile.set_theme(Theme::Dark as u8).unwrap();
+        assert_eq!(user_profile.theme, Theme::Dark as u8);
+
+        assert_eq!(user_profile.set_theme(200), Err(NetworkingError::InvalidTheme.into()));
+        assert_eq!(user_profile.theme, Theme::Dark as u8);
+    }
+
+    #[test]
+    fn test_quote_comment() {
+        let author = Pubkey::new_unique();
+        let commenter = Pubkey::new_unique();
+        let quoter = Pubkey::new_unique();
+        let mut source_profile = profile_with_friends(author, &[]);
+        let mut quoter_profile = profile_with_friends(quoter, &[]);
+        source_profile.add_post(author, "Hello World!".to_string(), vec![], vec![], 0);
+        source_profile.add_comment(author, 0, commenter, "Great insight".to_string()).unwrap();
+
+        let kind = source_profile.quote_comment(author, 0, 0).unwrap();
+        quoter_profile.add_quote_post(quoter, Some("So true".to_string()), kind.clone(), 0);
+        source_profile.quote_comment(author, 0, 0).unwrap();
+
+        let quote = &quoter_profile.posts[&quoter][0];
+        assert_eq!(quote.content, "So true");
+        assert_eq!(
+            quote.kind,
+            PostKind::Quote {
+                source_post_author: author,
+                source_post_index: 0,
+                source_comment_index: 0,
+                quoted_hash: hashv(&[b"Great insight"]).to_bytes(),
+            }
+        );
+        assert_eq!(source_profile.posts[&author][0].comments[0].quote_count.get(), 2);
+
+        assert_eq!(
+            source_profile.quote_comment(author, 0, 1),
+            Err(NetworkingError::CommentNotFound.into())
+        );
+        assert_eq!(
+            source_profile.quote_comment(author, 1, 0),
+            Err(NetworkingError::PostNotFound.into())
+        );
+    }
+
+    #[test]
+    fn test_all_contacts() {
+        let me = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let referrer = Pubkey::new_unique();
+        let commenter = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(me, &[friend]);
+        user_profile.referred_by = Some(referrer);
+        user_profile.add_post(me, "Hello World!".to_string(), vec![], vec![], 0);
+        user_profile.add_comment(me, 0, commenter, "Nice post!".to_string()).unwrap();
+        user_profile.add_comment(me, 0, me, "Thanks!".to_string()).unwrap();
+        user_profile.add_comment(me, 0, friend, "Agreed".to_string()).unwrap();
+
+        assert_eq!(
+            user_profile.all_contacts(),
+            [friend, referrer, commenter].into_iter().collect::<HashSet<_>>()
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_sponsor_pays_for_nft_accounts() {
+        use crate::client::{build_instructions, Action, NftAccounts};
+
         let program_id = Pubkey::new_unique();
-        let mut test = ProgramTest::new("professional_networking", program_id, processor!(process_instruction));
-        let user_account = Keypair::new();
-        let friend_account = Keypair::new();
-        test.add_account(user_account.pubkey(), Account::new(0, 0, &program_id));
-        test.add_account(friend_account.pubkey(), Account::new(0, 0, &program_id));
-        let (mut banks_client, payer, recent_blockhash) = test.start().await;
+        let sponsor = Pubkey::new_unique();
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let user = Pubkey::new_unique();
+        let mut existing = HashMap::new();
+        existing.insert(user, profile_with_friends(user, &friends[1..]));
+        existing.insert(friends[0], profile_with_friends(friends[0], &[]));
+
+        let nft = NftAccounts {
+            mint: Pubkey::new_unique(),
+            token_account: Pubkey::new_unique(),
+        };
+        let accept = Action::AcceptFriendRequest {
+            user,
+            friend: friends[0],
+            nft: Some(nft),
+        };
+        let instructions = build_instructions(&program_id, &sponsor, &existing, &[accept]).unwrap();
+        let accounts = &instructions[0].accounts;
+
+        assert_eq!(accounts[0].pubkey, user);
+        assert_eq!(accounts[3].pubkey, sponsor);
+        assert!(accounts[3].is_signer && accounts[3].is_writable);
+        assert_eq!(accounts.last().unwrap().pubkey, friends[0]);
+    }
+
+    #[test]
+    fn test_deactivated_profile_cannot_post() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = profile_account_data(&member_profile(user));
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+        let write_post = ProfessionalNetworkingInstruction::WritePost { content: "Hello".to_string(), tags: vec![], attachments: vec![] };
+        let is_active = || UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap().is_active();
+
+        assert_eq!(run(&program_id, &accounts, &ProfessionalNetworkingInstruction::DeactivateProfile), Ok(()));
+        assert!(!is_active());
+        assert_eq!(run(&program_id, &accounts, &write_post), Err(NetworkingError::ProfileDeactivated.into()));
+
+        assert_eq!(run(&program_id, &accounts, &ProfessionalNetworkingInstruction::ReactivateProfile), Ok(()));
+        assert!(is_active());
+        assert_eq!(run(&program_id, &accounts, &write_post), Ok(()));
+    }
+
+    #[test]
+    fn test_legacy_profile_is_active() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let profile = profile_with_friends(user, &[]);
+        // Поля от флага деактивации до конца профиля появились позже
+        let added_len = (
+            profile.deactivated,
+            profile.user_reports.clone(),
+            profile.flagged,
+            profile.membership_expires_at,
+            profile.sections.clone(),
+            profile.verified_domain.clone(),
+        )
+            .try_to_vec()
+            .unwrap()
+            .len()
+            + retention_fields_len(&profile);
+        let mut lamports = 0;
+        let mut data = legacy_profile_data(&profile, added_len);
+        assert!(UserProfile::load(&data).unwrap().is_active());
+
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+        let send = ProfessionalNetworkingInstruction::SendFriendRequest { friend_address: Pubkey::new_unique() };
+        assert_eq!(run(&program_id, &accounts, &send), Ok(()));
+    }
+
+    #[test]
+    fn test_cannot_accept_deactivated_friend() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let mut friend_profile = profile_with_friends(friend, &[user]);
+        friend_profile.deactivated = true;
+        let (mut user_lamports, mut friend_lamports) = (0, 0);
+        let mut user_data = profile_account_data(&profile_with_friends(user, &[]));
+        let mut friend_data = profile_account_data(&friend_profile);
+        let (user_before, friend_before) = (user_data.clone(), friend_data.clone());
+        let accounts = [
+            AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &program_id, false, 0),
+            AccountInfo::new(&friend, false, true, &mut friend_lamports, &mut friend_data, &program_id, false, 0),
+        ];
+
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: friend };
+        assert_eq!(run(&program_id, &accounts, &accept), Err(NetworkingError::ProfileDeactivated.into()));
+        assert_eq!(*accounts[0].try_borrow_data().unwrap(), &user_before[..]);
+        assert_eq!(*accounts[1].try_borrow_data().unwrap(), &friend_before[..]);
+    }
 
-        let send_friend_request_data = ProfessionalNetworkingInstruction::SendFriendRequest {
-            friend_address: friend_account.pubkey(),
+    #[test]
+    fn test_reputation() {
+        let me = Pubkey::new_unique();
+        let friends: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        let mut user_profile = profile_with_friends(me, &friends);
+        user_profile.nft_owned = true;
+        user_profile.add_post(me, "First".to_string(), vec![], vec![], 0);
+        user_profile.add_post(me, "Second".to_string(), vec![], vec![], 0);
+        user_profile.add_comment(me, 0, friends[0], "Nice".to_string()).unwrap();
+        user_profile.add_comment(me, 1, friends[1], "Great".to_string()).unwrap();
+        user_profile.add_comment(me, 1, friends[2], "Agreed".to_string()).unwrap();
+        user_profile.posts.get_mut(&me).unwrap()[0].impressions = Counter::new(25);
+
+        // 6 * 10 + 50 + 2 * 5 + 3 * 2 + 25 / 10
+        assert_eq!(user_profile.reputation(), 128);
+    }
+
+    #[test]
+    fn test_report_user_flags_at_threshold() {
+        let target = Pubkey::new_unique();
+        let mut target_profile = profile_with_friends(target, &[]);
+        let reporters: Vec<Pubkey> = (0..USER_REPORT_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+
+        for reporter in &reporters[..USER_REPORT_THRESHOLD - 1] {
+            target_profile.report(*reporter, 1).unwrap();
+            target_profile.report(*reporter, 2).unwrap();
         }
-        .try_to_vec()
-        .unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &send_friend_request_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
+        assert_eq!(target_profile.user_reports.len(), USER_REPORT_THRESHOLD - 1);
+        assert!(target_profile.user_reports.iter().all(|(_, reason)| *reason == 2));
+        assert!(!target_profile.flagged);
+
+        target_profile.report(reporters[USER_REPORT_THRESHOLD - 1], 1).unwrap();
+        assert!(target_profile.flagged);
+
+        assert_eq!(target_profile.report(target, 1), Err(NetworkingError::SelfReport.into()));
+    }
+
+    #[test]
+    fn test_membership_expiry_and_renewal() {
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &friends);
+        let claimed_at = 1_000;
+        user_profile.grant_membership(claimed_at);
+        assert!(user_profile.can_write_post(claimed_at));
+
+        let after_expiry = claimed_at + MEMBERSHIP_DURATION_SECS;
+        assert!(!user_profile.can_write_post(after_expiry));
+        assert!(!user_profile.can_comment(after_expiry));
+        assert!(user_profile.nft_owned);
+
+        let threshold = NetworkConfig::new(Pubkey::new_unique(), vec![]).membership_threshold();
+        assert_eq!(
+            user_profile.renew_membership(after_expiry, threshold - 1, threshold),
+            Err(NetworkingError::NotEligible.into())
         );
-        transaction.sign(&[&payer, &user_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
-        let accept_friend_request_data = ProfessionalNetworkingInstruction::AcceptFriendRequest {
-            friend_address: user_account.pubkey(),
+        user_profile.renew_membership(after_expiry, threshold, threshold).unwrap();
+        assert!(user_profile.can_write_post(after_expiry));
+        assert_eq!(user_profile.membership_expires_at, after_expiry + MEMBERSHIP_DURATION_SECS);
+    }
+
+    #[test]
+    fn test_renewal_counts_only_confirmed_friends() {
+        let program_id = Pubkey::new_unique();
+        let me = Pubkey::new_unique();
+        let (mutual, one_sided) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // SendFriendRequest добавляет друга только в свой профиль
+        let profile = profile_with_friends(me, &[mutual, one_sided]);
+        let (mut mutual_lamports, mut one_sided_lamports) = (0, 0);
+        let mut mutual_data = profile_with_friends(mutual, &[me]).try_to_vec().unwrap();
+        let mut one_sided_data = profile_with_friends(one_sided, &[]).try_to_vec().unwrap();
+        let mutual_account = AccountInfo::new(&mutual, false, false, &mut mutual_lamports, &mut mutual_data, &program_id, false, 0);
+        let one_sided_account =
+            AccountInfo::new(&one_sided, false, false, &mut one_sided_lamports, &mut one_sided_data, &program_id, false, 0);
+
+        let accounts = [mutual_account.clone(), one_sided_account];
+        assert_eq!(count_confirmed_friends(&profile, &accounts, &program_id), Ok(1));
+        assert_eq!(
+            count_confirmed_friends(&profile, &[mutual_account.clone(), mutual_account], &program_id),
+            Err(NetworkingError::DuplicateAccount.into())
+        );
+
+        let mut config = NetworkConfig::new(Pubkey::new_unique(), vec![]);
+        assert_eq!(config.membership_threshold(), NFT_FRIEND_THRESHOLD);
+        config.membership_friend_threshold = 0;
+        assert_eq!(config.membership_threshold(), NFT_FRIEND_THRESHOLD);
+        config.membership_friend_threshold = 2;
+        assert_eq!(config.membership_threshold(), 2);
+    }
+
+    #[test]
+    fn test_profile_sections() {
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        user_profile.add_section("About".to_string(), "Rust developer".to_string()).unwrap();
+        user_profile.add_section("Speaking".to_string(), "RustConf".to_string()).unwrap();
+
+        user_profile.reorder_sections(&[1, 0]).unwrap();
+        assert_eq!(user_profile.sections[0].title, "Speaking");
+        assert_eq!(user_profile.sections[0].order, 0);
+        assert_eq!(user_profile.sections[1].title, "About");
+        assert_eq!(user_profile.sections[1].order, 1);
+
+        for new_order in [&[0, 0][..], &[0][..], &[0, 2][..]] {
+            assert_eq!(
+                user_profile.reorder_sections(new_order),
+                Err(NetworkingError::InvalidSectionOrder.into())
+            );
         }
-        .try_to_vec()
-        .unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &accept_friend_request_data, vec![friend_account.pubkey()])],
-            Some(&payer.pubkey()),
+
+        user_profile
+            .update_section(1, "About".to_string(), "Rust and Solana developer".to_string())
+            .unwrap();
+        assert_eq!(user_profile.sections[1].body, "Rust and Solana developer");
+        assert_eq!(user_profile.sections[1].order, 1);
+        assert_eq!(
+            user_profile.update_section(1, "About".to_string(), "x".repeat(MAX_SECTION_BODY_LEN + 1)),
+            Err(NetworkingError::SectionTooLong.into())
         );
-        transaction.sign(&[&payer, &friend_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
 
-        assert!(user_profile.friends.contains(&friend_account.pubkey()));
+        user_profile.remove_section(0).unwrap();
+        assert_eq!(user_profile.sections[0].title, "About");
+        assert_eq!(user_profile.sections[0].order, 0);
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("Rust").unwrap(), "rust");
+        assert_eq!(normalize_tag(" rust ").unwrap(), "rust");
+        assert_eq!(normalize_tag("RUST").unwrap(), normalize_tag("rust").unwrap());
+        assert_eq!(normalize_tag("web_3").unwrap(), "web_3");
+
+        assert_eq!(normalize_tag("раст"), Err(NetworkingError::InvalidTag));
+        assert_eq!(normalize_tag("café"), Err(NetworkingError::InvalidTag));
+        assert_eq!(normalize_tag("c++"), Err(NetworkingError::InvalidTag));
+        assert_eq!(normalize_tag("two words"), Err(NetworkingError::InvalidTag));
+        assert_eq!(normalize_tag("r"), Err(NetworkingError::InvalidTag));
+        assert_eq!(normalize_tag(&"a".repeat(MAX_TAG_LEN + 1)), Err(NetworkingError::InvalidTag));
+    }
+
+    #[test]
+    fn test_post_relevance_decays_with_age() {
+        let author = Pubkey::new_unique();
+        let now = 10 * RELEVANCE_DECAY_SECS as i64;
+        let mut old_post = Post::new(author, "Old".to_string(), vec![], now - 3 * RELEVANCE_DECAY_SECS as i64);
+        let mut new_post = Post::new(author, "New".to_string(), vec![], now - 3600);
+        for post in [&mut old_post, &mut new_post] {
+            post.add_comment(Pubkey::new_unique(), "Nice post!".to_string()).unwrap();
+            post.impressions = Counter::new(20);
+        }
+
+        assert!(new_post.relevance(now) > old_post.relevance(now));
+        assert_eq!(Post::new(author, "Fresh".to_string(), vec![], now).relevance(now), 1.0);
     }
 
-    async fn test_write_post() {
+    #[test]
+    fn test_verify_domain_admin_only() {
         let program_id = Pubkey::new_unique();
-        let mut test = ProgramTest::new("professional_networking", program_id, processor!(process_instruction));
-        let user_account = Keypair::new();
-        test.add_account(user_account.pubkey(), Account::new(0, 0, &program_id));
-     
-        let (mut banks_client, payer, recent_blockhash) = test.start().await;
-        let content = "Hello World!".to_string();
-        let write_post_data = ProfessionalNetworkingInstruction::WritePost { content }.try_to_vec().unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &write_post_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
-        );
-        transaction.sign(&[&payer, &user_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
+        let admin = Pubkey::new_unique();
+        let oracle = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let config_key = config_address(&program_id).0;
 
-        assert_eq!(user_profile.posts.len(), 1);
-        assert_eq!(user_profile.posts.get(&user_account.pubkey()).unwrap().len(), 1);
-        assert_eq!(user_profile.posts.get(&user_account.pubkey()).unwrap()[0].content, content);
+        let (mut admin_lamports, mut oracle_lamports, mut config_lamports) = (0, 0, 0);
+        let (mut target_lamports, mut other_lamports) = (0, 0);
+        let (mut admin_data, mut oracle_data) = (vec![], vec![]);
+        let mut config_data = config_account_data(&NetworkConfig::new(admin, vec![oracle]));
+        let mut target_data = profile_with_friends(target, &[]).try_to_vec().unwrap();
+        target_data.resize(target_data.len() + 64, 0);
+        // Под ключом other лежит профиль target
+        let mut other_data = target_data.clone();
+        let admin_info = AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0);
+        let oracle_info = AccountInfo::new(&oracle, true, false, &mut oracle_lamports, &mut oracle_data, &program_id, false, 0);
+        let config_info = AccountInfo::new(&config_key, false, false, &mut config_lamports, &mut config_data, &program_id, false, 0);
+        let target_info = AccountInfo::new(&target, false, true, &mut target_lamports, &mut target_data, &program_id, false, 0);
+        let other_info = AccountInfo::new(&other, false, true, &mut other_lamports, &mut other_data, &program_id, false, 0);
+        let by_oracle = [oracle_info, config_info.clone(), target_info.clone()];
+        let wrong_account = [admin_info.clone(), config_info.clone(), other_info.clone()];
+        let by_admin = [admin_info, config_info, target_info.clone()];
+        let verify = |target| {
+            ProfessionalNetworkingInstruction::VerifyDomain { target, domain: "Example.com".to_string() }.try_to_vec().unwrap()
+        };
+
+        assert_eq!(process_instruction(&program_id, &by_oracle, &verify(target)), Err(NetworkingError::Unauthorized.into()));
+        assert_eq!(process_instruction(&program_id, &wrong_account, &verify(target)), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            process_instruction(&program_id, &wrong_account, &verify(other)),
+            Err(NetworkingError::ProfileAddressMismatch.into())
+        );
+        assert_eq!(UserProfile::load(&other_info.try_borrow_data().unwrap()).unwrap().verified_domain, None);
+        assert_eq!(process_instruction(&program_id, &by_admin, &verify(target)), Ok(()));
+        let verified = UserProfile::load(&target_info.try_borrow_data().unwrap()).unwrap().verified_domain;
+        assert_eq!(verified.as_deref(), Some("example.com"));
+
+        assert_eq!(normalize_domain(" Anthropic.COM ").unwrap(), "anthropic.com");
+        assert_eq!(normalize_domain("mail.example-corp.io").unwrap(), "mail.example-corp.io");
+        assert_eq!(normalize_domain("localhost"), Err(NetworkingError::InvalidDomain));
+        assert_eq!(normalize_domain("bad..com"), Err(NetworkingError::InvalidDomain));
+        assert_eq!(normalize_domain("-bad.com"), Err(NetworkingError::InvalidDomain));
+        assert_eq!(normalize_domain("exa mple.com"), Err(NetworkingError::InvalidDomain));
+    }
+
+    #[test]
+    fn test_double_accept_is_idempotent() {
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let mut alice_profile = profile_with_friends(alice, &[bob]);
+        let mut bob_profile = profile_with_friends(bob, &[alice]);
+
+        // Оба приняли заявки друг друга, затем третье принятие
+        assert!(!alice_profile.link_friends(&mut bob_profile).unwrap());
+        assert!(!bob_profile.link_friends(&mut alice_profile).unwrap());
+        assert!(!alice_profile.link_friends(&mut bob_profile).unwrap());
+        assert_eq!(alice_profile.friends.len(), 1);
+        assert_eq!(bob_profile.friends.len(), 1);
+
+        let carol = Pubkey::new_unique();
+        let mut carol_profile = profile_with_friends(carol, &[]);
+        assert!(alice_profile.link_friends(&mut carol_profile).unwrap());
+        assert!(!carol_profile.link_friends(&mut alice_profile).unwrap());
+        assert_eq!(alice_profile.friends.len(), 2);
+        assert_eq!(carol_profile.friends.len(), 1);
+    }
+
+    #[test]
+    fn test_reaches_nft_threshold_with() {
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &friends[1..]);
+
+        assert!(user_profile.reaches_nft_threshold_with(&friends[0]));
+        assert!(!user_profile.reaches_nft_threshold_with(&friends[1]));
+
+        user_profile.grant_membership(0);
+        assert!(!user_profile.reaches_nft_threshold_with(&friends[0]));
     }
 
-    #[tokio::test]
-    async fn test_add_comment() {
+    #[test]
+    fn test_integrity_check_reports_corruption() {
+        let address = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(address, &[address]);
+        user_profile.name = "Alice".to_string();
+        for order in 0..=MAX_PROFILE_SECTIONS as u8 {
+            user_profile.sections.push(ProfileSection {
+                title: "t".to_string(),
+                body: String::new(),
+                order,
+            });
+        }
+        let mut data = user_profile.try_to_vec().unwrap();
+        data.resize(data.len() + 64, 0);
+
+        let report = UserProfile::integrity_check(&data);
+        assert_eq!(
+            report.issues,
+            vec![
+                IntegrityIssue::SelfInFriends,
+                IntegrityIssue::OverCap {
+                    field: "sections",
+                    len: MAX_PROFILE_SECTIONS + 1,
+                    max: MAX_PROFILE_SECTIONS,
+                },
+            ]
+        );
+
+        // Имя "Alice" идёт сразу после флага и длины
+        data[5] = 0xff;
+        assert_eq!(
+            UserProfile::integrity_check(&data).issues,
+            vec![IntegrityIssue::InvalidUtf8 { field: "name", offset: 1 }]
+        );
+
+        let mut over_attached = profile_with_friends(address, &[]);
+        let attachments = vec![attachment("https://example.com/a.png"); MAX_POST_ATTACHMENTS + 1];
+        over_attached.add_post(address, "post".to_string(), vec![], attachments, 0);
+        assert_eq!(
+            UserProfile::integrity_check(&over_attached.try_to_vec().unwrap()).issues,
+            vec![IntegrityIssue::OverCap {
+                field: "post.attachments",
+                len: MAX_POST_ATTACHMENTS + 1,
+                max: MAX_POST_ATTACHMENTS,
+            }]
+        );
+
+        let mut oversized_contacts = profile_with_friends(address, &[]);
+        oversized_contacts.private_contact = vec![1; MAX_CONTACT_BLOB_LEN + 1];
+        oversized_contacts.shared_contacts.push((Pubkey::new_unique(), vec![1; MAX_CONTACT_BLOB_LEN + 2]));
+        assert_eq!(
+            UserProfile::integrity_check(&oversized_contacts.try_to_vec().unwrap()).issues,
+            vec![
+                IntegrityIssue::OverCap {
+                    field: "private_contact",
+                    len: MAX_CONTACT_BLOB_LEN + 1,
+                    max: MAX_CONTACT_BLOB_LEN,
+                },
+                IntegrityIssue::OverCap {
+                    field: "shared_contacts.blob",
+                    len: MAX_CONTACT_BLOB_LEN + 2,
+                    max: MAX_CONTACT_BLOB_LEN,
+                },
+            ]
+        );
+
+        let valid = profile_with_friends(address, &[]).try_to_vec().unwrap();
+        assert!(UserProfile::integrity_check(&valid).is_ok());
+        assert_eq!(
+            UserProfile::integrity_check(&valid[..valid.len() - 3]).issues,
+            vec![IntegrityIssue::Truncated { field: "requests_accepted", offset: valid.len() - 5 }]
+        );
+    }
+
+    #[test]
+    fn test_integrity_check_locates_bad_field() {
+        let address = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(address, &[Pubkey::new_unique()]);
+        user_profile.add_post(address, "post".to_string(), vec!["tag".to_string()], vec![], 0);
+        user_profile.sections.push(ProfileSection { title: "t".to_string(), body: "b".to_string(), order: 0 });
+        user_profile.verified_domain = Some("example.com".to_string());
+        user_profile.available_hours = Some((9, 18));
+        let data = user_profile.try_to_vec().unwrap();
+        // Обход полей должен совпадать с раскладкой структуры
+        assert_eq!(UserProfile::walk_fields(&data), Ok(data.len()));
+
+        let mut bad_flag = data.clone();
+        bad_flag[0] = 2;
+        assert_eq!(UserProfile::integrity_check(&bad_flag).issues, vec![IntegrityIssue::Undecodable { field: "is_initialized", offset: 0 }]);
+
+        // Последняя буква домена: за ней max_retained_posts, membership_nft, encryption_pubkey,
+        // private_contact, shared_contacts, identicon_seed, timezone_offset, счётчики и часы
+        let tail = 2 + 1 + 1 + 4 + 4 + 8 + 1 + 4 + 4 + 3;
+        let domain_offset = data.len() - tail - "example.com".len() - 4 - 1;
+        let mut bad_domain = data.clone();
+        bad_domain[data.len() - tail - 1] = 0xff;
+        assert_eq!(UserProfile::integrity_check(&bad_domain).issues, vec![IntegrityIssue::InvalidUtf8 { field: "verified_domain", offset: domain_offset }]);
+
+        assert!(matches!(UserProfile::integrity_check(&data[..domain_offset + 3]).issues[..], [IntegrityIssue::Truncated { field: "verified_domain", offset }] if offset == domain_offset));
+    }
+
+    #[test]
+    fn test_load_rejects_empty_and_oversized_accounts() {
+        let not_initialized = Err(NetworkingError::ProfileNotInitialized.into());
+        assert_eq!(UserProfile::load(&[]).map(|_| ()), not_initialized);
+        assert_eq!(UserProfile::load(&[0; 512]).map(|_| ()), not_initialized);
+
+        let mut oversized = vec![0; MAX_PROFILE_ACCOUNT_LEN + 1];
+        oversized[0] = 1;
+        assert_eq!(
+            UserProfile::load(&oversized).map(|_| ()),
+            Err(NetworkingError::AccountTooLarge.into())
+        );
+
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        user_profile.name = "Alice".to_string();
+        let data = user_profile.try_to_vec().unwrap();
+        // Обрыв посреди имени
+        assert_eq!(
+            UserProfile::load(&data[..7]).map(|_| ()),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut padded = data.clone();
+        padded.resize(1024, 0);
+        assert_eq!(UserProfile::load(&padded).unwrap().name, "Alice");
+    }
+
+    #[test]
+    fn test_friends_page_is_sorted_and_stable() {
+        let friends: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        let mut shuffled = friends.clone();
+        shuffled.reverse();
+        shuffled.swap(1, 4);
+        let user_profile = profile_with_friends(Pubkey::new_unique(), &shuffled);
+
+        let mut sorted = friends.clone();
+        sorted.sort();
+        let pages: Vec<Pubkey> = (0..3)
+            .flat_map(|page| user_profile.friends_page(page * 3, 3))
+            .collect();
+        assert_eq!(pages, sorted);
+        assert_eq!(user_profile.friends_page(3, 3), user_profile.friends_page(3, 3));
+        assert_eq!(user_profile.friends_page(6, 3).len(), 1);
+        assert!(user_profile.friends_page(10, 3).is_empty());
+    }
+
+    #[test]
+    fn test_retention_policy_evicts_oldest_posts() {
+        let author = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(author, &[]);
+        user_profile.set_retention_policy(2).unwrap();
+
+        assert!(user_profile.add_post(author, "first".to_string(), vec!["rust".to_string()], vec![], 1).is_empty());
+        user_profile.add_comment(author, 0, Pubkey::new_unique(), "nice".to_string()).unwrap();
+        assert!(user_profile.add_post(author, "second".to_string(), vec![], vec![], 2).is_empty());
+        assert_eq!(user_profile.add_post(author, "third".to_string(), vec![], vec![], 3), vec![0]);
+
+        let posts = &user_profile.posts[&author];
+        assert_eq!(posts.len(), 3);
+        assert!(posts[0].evicted);
+        assert!(posts[0].content.is_empty() && posts[0].tags.is_empty());
+        // Структура комментариев остаётся, чтобы цитаты не ссылались в пустоту
+        assert_eq!(posts[0].comments.len(), 1);
+        assert!(posts[0].comments[0].content.is_empty());
+        assert!(!posts[1].evicted && !posts[2].evicted);
+        assert_eq!(
+            user_profile.add_comment(author, 0, Pubkey::new_unique(), "late".to_string()),
+            Err(NetworkingError::PostNotFound.into())
+        );
+        assert_eq!(user_profile.quote_comment(author, 0, 0), Err(NetworkingError::PostNotFound.into()));
+        assert_eq!(
+            user_profile.posts.get_mut(&author).unwrap()[0].record_impression(&Pubkey::new_unique(), 0),
+            Err(NetworkingError::PostNotFound.into())
+        );
+        let posts = &user_profile.posts[&author];
+        assert_eq!((posts[0].comments[0].quote_count.get(), posts[0].impressions.get()), (0, 0));
+
+        assert_eq!(user_profile.add_post(author, "fourth".to_string(), vec![], vec![], 4), vec![1]);
+        user_profile.set_retention_policy(3).unwrap();
+        assert!(user_profile.add_post(author, "fifth".to_string(), vec![], vec![], 5).is_empty());
+
+        assert_eq!(
+            user_profile.set_retention_policy(0),
+            Err(NetworkingError::InvalidRetentionPolicy.into())
+        );
+        assert_eq!(
+            user_profile.set_retention_policy(MAX_RETAINED_POSTS_CEILING + 1),
+            Err(NetworkingError::InvalidRetentionPolicy.into())
+        );
+    }
+
+    // Аккаунт профиля, записанного до появления последних added_len байт полей:
+    // на их месте нули, как в хвосте старого аккаунта
+    fn legacy_profile_data(profile: &UserProfile, added_len: usize) -> Vec<u8> {
+        let mut data = profile.try_to_vec().unwrap();
+        data.truncate(data.len() - added_len);
+        data.resize(data.len() + added_len + 1024, 0);
+        data
+    }
+
+    // Сериализованная длина полей от max_retained_posts до конца профиля
+    fn retention_fields_len(profile: &UserProfile) -> usize {
+        (
+            profile.max_retained_posts,
+            profile.membership_nft,
+            profile.encryption_pubkey,
+            profile.private_contact.clone(),
+            profile.shared_contacts.clone(),
+            profile.identicon_seed,
+            profile.timezone_offset,
+            profile.requests_sent,
+            profile.requests_accepted,
+            profile.available_hours,
+        )
+            .try_to_vec()
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn test_legacy_profile_keeps_default_retention() {
+        let author = Pubkey::new_unique();
+        let profile = profile_with_friends(author, &[]);
+        let data = legacy_profile_data(&profile, retention_fields_len(&profile));
+
+        let mut legacy = UserProfile::load(&data).unwrap();
+        assert_eq!(legacy.max_retained_posts, 0);
+        assert_eq!(legacy.retention_limit(), DEFAULT_MAX_RETAINED_POSTS as usize);
+        for timestamp in 0..3 {
+            assert!(legacy.add_post(author, "post".to_string(), vec![], vec![], timestamp).is_empty());
+        }
+        assert!(legacy.posts[&author].iter().all(|post| !post.evicted));
+    }
+
+    #[test]
+    fn test_write_post_past_retention_limit_logs_eviction() {
         let program_id = Pubkey::new_unique();
-        let mut test = ProgramTest::new("professional_networking", program_id, processor!(process_instruction));
-        let user_account = Keypair::new();
-        test.add_account(user_account.pubkey(), Account::new(0, 0, &program_id));
-        let (mut banks_client, payer, recent_blockhash) = test.start().await;
-        let content = "Hello World!".to_string();
-        let write_post_data = ProfessionalNetworkingInstruction::WritePost { content }.try_to_vec().unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &write_post_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
-        );
-        transaction.sign(&[&payer, &user_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
-
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
-
-        let post_author = user_account.pubkey();
-        let post_index = 0; 
-        let comment_content = "Nice post!".to_string();
-        let add_comment_data = ProfessionalNetworkingInstruction::AddComment {
-            post_author,
-            post_index,
-            content: comment_content.clone(),
+        let user = Pubkey::new_unique();
+        let mut profile = member_profile(user);
+        profile.set_retention_policy(1).unwrap();
+        profile.add_post(user, "old".to_string(), vec![], vec![], TEST_NOW - 10);
+        let mut lamports = 0;
+        let mut data = profile_account_data(&profile);
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let write_post = ProfessionalNetworkingInstruction::WritePost { content: "new".to_string(), tags: vec![], attachments: vec![] };
+        let (result, logs) = run_logged(&program_id, &accounts, &write_post);
+        assert_eq!(result, Ok(()));
+        assert!(logs.contains(&format!("PostEvicted: author={} index=0", user)));
+
+        let stored = UserProfile::load(&accounts[0].try_borrow_data().unwrap()).unwrap();
+        let posts = &stored.posts[&user];
+        assert!(posts[0].evicted && posts[0].content.is_empty());
+        assert_eq!((posts[1].evicted, posts[1].content.as_str()), (false, "new"));
+    }
+
+    #[test]
+    fn test_projected_size_after_matches_growth() {
+        let author = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(author, &[]);
+
+        let sizes: Vec<usize> = (0..5).map(|n| user_profile.projected_size_after(n, 40)).collect();
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let projected = user_profile.projected_size_after(3, 40);
+        for timestamp in 0..3 {
+            user_profile.add_post(author, "x".repeat(40), vec![], vec![], timestamp);
+        }
+        assert_eq!(user_profile.try_to_vec().unwrap().len(), projected);
+        assert_eq!(user_profile.projected_size_after(0, 40), projected);
+    }
+
+    #[test]
+    fn test_admin_repair_account() {
+        fn repair(program_id: &Pubkey, signer: &Pubkey, config: &NetworkConfig, target: &mut [u8], data: &[u8]) -> ProgramResult {
+            let config_key = Pubkey::find_program_address(&[CONFIG_SEED], program_id).0;
+            let target_key = Pubkey::new_unique();
+            let mut config_data = config.try_to_vec().unwrap();
+            config_data.resize(NetworkConfig::LEN, 0);
+            let (mut signer_lamports, mut config_lamports, mut target_lamports) = (0, 0, 0);
+            let mut signer_data = vec![];
+            let accounts = [
+                AccountInfo::new(signer, true, true, &mut signer_lamports, &mut signer_data, program_id, false, 0),
+                AccountInfo::new(&config_key, false, true, &mut config_lamports, &mut config_data, program_id, false, 0),
+                AccountInfo::new(&target_key, false, true, &mut target_lamports, target, program_id, false, 0),
+            ];
+            process_instruction(program_id, &accounts, data)
+        }
+
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let mut config = NetworkConfig::new(admin, vec![]);
+        let profile = profile_with_friends(Pubkey::new_unique(), &[]).try_to_vec().unwrap();
+        // Устаревший хвост и сброшенный первый байт
+        let mut corrupted = profile.clone();
+        corrupted.extend([0xAB; 16]);
+        corrupted[0] = 0;
+        assert!(UserProfile::load(&corrupted).is_err());
+
+        let patch = vec![
+            RepairOp::SetVersionByte { v: 1 },
+            RepairOp::ZeroTail { from: profile.len() as u32 },
+        ];
+        let data = ProfessionalNetworkingInstruction::AdminRepairAccount { patch }.try_to_vec().unwrap();
+        let mut target = corrupted.clone();
+        assert_eq!(
+            repair(&program_id, &admin, &config, &mut target, &data),
+            Err(NetworkingError::RepairsDisabled.into())
+        );
+        config.repairs_enabled = true;
+        assert_eq!(
+            repair(&program_id, &Pubkey::new_unique(), &config, &mut target, &data),
+            Err(NetworkingError::Unauthorized.into())
+        );
+        assert_eq!(target, corrupted);
+
+        assert_eq!(repair(&program_id, &admin, &config, &mut target, &data), Ok(()));
+        assert!(UserProfile::load(&target).is_ok());
+        assert_eq!(&target[..profile.len()], &profile[..]);
+        assert!(target[profile.len()..].iter().all(|byte| *byte == 0));
+
+        // Операции проверяются против размера аккаунта, в том числе после TruncateTo
+        let len = target.len();
+        assert!(validate_repair_ops(&[RepairOp::TruncateTo { len: 10 }, RepairOp::ZeroTail { from: 10 }], len).is_ok());
+        assert_eq!(
+            validate_repair_ops(&[RepairOp::TruncateTo { len: 10 }, RepairOp::ZeroTail { from: 11 }], len),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            validate_repair_ops(&[RepairOp::TruncateTo { len: len as u32 + 1 }], len),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(validate_repair_ops(&[RepairOp::SetVersionByte { v: 1 }], 0), Err(ProgramError::InvalidArgument));
+        assert!(validate_repair_ops(&[RepairOp::SetVersionByte { v: 0 }], len).is_ok());
+        assert_eq!(validate_repair_ops(&[RepairOp::SetVersionByte { v: 2 }], len), Err(ProgramError::InvalidArgument));
+
+        // Неизвестный вариант операции не декодируется: тег, длина списка (4 байта), тег операции
+        let mut unknown_op = data.clone();
+        unknown_op[5] = 3;
+        assert_eq!(
+            repair(&program_id, &admin, &config, &mut target, &unknown_op),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip_and_address() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(owner, &[Pubkey::new_unique()]);
+        user_profile.add_post(owner, "hello".to_string(), vec!["rust".to_string()], vec![], 7);
+
+        // Архив хранит ровно сериализованный профиль
+        let snapshot = UserProfile::load(&user_profile.try_to_vec().unwrap())
+            .unwrap()
+            .try_to_vec()
+            .unwrap();
+        let archived = UserProfile::load(&snapshot).unwrap();
+        assert_eq!(archived.try_to_vec().unwrap(), user_profile.try_to_vec().unwrap());
+        assert_eq!(archived.posts[&owner][0].content, "hello");
+
+        let (first, _) = snapshot_address(&program_id, &owner, 100);
+        assert_eq!(snapshot_address(&program_id, &owner, 100).0, first);
+        assert_ne!(snapshot_address(&program_id, &owner, 101).0, first);
+        assert_ne!(snapshot_address(&Pubkey::new_unique(), &owner, 100).0, first);
+    }
+
+    #[test]
+    fn test_snapshot_cannot_stand_in_for_live_profile() {
+        let program_id = Pubkey::new_unique();
+        let referrer = Pubkey::new_unique();
+        let referred = Pubkey::new_unique();
+        let snapshot_key = snapshot_address(&program_id, &referrer, 100).0;
+        let mut referred_profile = profile_with_friends(referred, &[]);
+        referred_profile.referred_by = Some(referrer);
+        referred_profile.nft_owned = true;
+
+        let (mut referred_lamports, mut snapshot_lamports) = (0, 0);
+        let mut referred_data = referred_profile.try_to_vec().unwrap();
+        let mut snapshot_data = profile_with_friends(referrer, &[]).try_to_vec().unwrap();
+        let (referred_before, snapshot_before) = (referred_data.clone(), snapshot_data.clone());
+        let accounts = [
+            AccountInfo::new(&referred, true, true, &mut referred_lamports, &mut referred_data, &program_id, false, 0),
+            AccountInfo::new(&snapshot_key, false, true, &mut snapshot_lamports, &mut snapshot_data, &program_id, false, 0),
+        ];
+
+        let claim = ProfessionalNetworkingInstruction::ClaimReferral;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &claim.try_to_vec().unwrap()),
+            Err(NetworkingError::ReferrerMismatch.into())
+        );
+        // Ключ совпадает с target, но в аккаунте лежит чужой профиль
+        let report = ProfessionalNetworkingInstruction::ReportUser { target: snapshot_key, reason_code: 1 };
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &report.try_to_vec().unwrap()),
+            Err(NetworkingError::ProfileAddressMismatch.into())
+        );
+        assert_eq!(*accounts[0].try_borrow_data().unwrap(), &referred_before[..]);
+        assert_eq!(*accounts[1].try_borrow_data().unwrap(), &snapshot_before[..]);
+    }
+
+    fn attachment(uri: &str) -> Attachment {
+        Attachment {
+            uri: uri.to_string(),
+            content_hash: [7; 32],
+            mime_hint: 1,
+        }
+    }
+
+    #[test]
+    fn test_post_attachments() {
+        let author = Pubkey::new_unique();
+        let attachments = vec![attachment("https://example.com/deck.pdf"), attachment("ipfs://bafy123")];
+        assert_eq!(validate_attachments(&attachments), Ok(()));
+
+        let mut user_profile = profile_with_friends(author, &[]);
+        user_profile.add_post(author, "Slides".to_string(), vec![], attachments.clone(), 0);
+        user_profile.add_comment(author, 0, Pubkey::new_unique(), "Thanks".to_string()).unwrap();
+        assert_eq!(user_profile.posts[&author][0].attachments, attachments);
+
+        for uri in ["http://example.com/a", "https://", "ftp://x", "https://a b", "ar://a\n"] {
+            assert_eq!(
+                validate_attachments(&[attachment(uri)]),
+                Err(NetworkingError::InvalidAttachmentUri.into())
+            );
+        }
+        let long_uri = format!("ar://{}", "a".repeat(MAX_ATTACHMENT_URI_LEN));
+        assert_eq!(
+            validate_attachments(&[attachment(&long_uri)]),
+            Err(NetworkingError::InvalidAttachmentUri.into())
+        );
+        assert_eq!(
+            validate_attachments(&vec![attachment("ar://a"); MAX_POST_ATTACHMENTS + 1]),
+            Err(NetworkingError::TooManyAttachments.into())
+        );
+    }
+
+    #[test]
+    fn test_nft_friend_ratio() {
+        let friends: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let me = profile_with_friends(Pubkey::new_unique(), &friends);
+
+        // Профиль последнего друга не найден и не учитывается
+        let friend_profiles: HashMap<Pubkey, UserProfile> = friends[..4]
+            .iter()
+            .enumerate()
+            .map(|(i, friend)| {
+                let mut profile = profile_with_friends(*friend, &[]);
+                profile.nft_owned = i % 2 == 0;
+                (*friend, profile)
+            })
+            .collect();
+        assert_eq!(nft_friend_ratio(&me, &friend_profiles), 0.5);
+
+        let loner = profile_with_friends(Pubkey::new_unique(), &[]);
+        assert_eq!(nft_friend_ratio(&loner, &friend_profiles), 0.0);
+    }
+
+    #[test]
+    fn test_load_config_rejects_foreign_program_pda() {
+        let program_id = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let mut data = NetworkConfig::new(Pubkey::new_unique(), vec![]).try_to_vec().unwrap();
+        let mut lamports = 0;
+
+        let (config_key, _) = config_address(&program_id);
+        let config_account =
+            AccountInfo::new(&config_key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert!(load_config(&config_account, &program_id).is_ok());
+
+        // Тот же сид, но под другой программой
+        let (foreign_key, _) = config_address(&other_program);
+        let mut foreign_data = data.clone();
+        let mut foreign_lamports = 0;
+        let foreign_account = AccountInfo::new(
+            &foreign_key,
+            false,
+            true,
+            &mut foreign_lamports,
+            &mut foreign_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            load_config(&foreign_account, &program_id).unwrap_err(),
+            ProgramError::InvalidSeeds
+        );
+    }
+
+    #[test]
+    fn test_reconcile_membership_with_token_account() {
+        let owner = Pubkey::new_unique();
+        let nft = MembershipNft {
+            mint: Pubkey::new_unique(),
+            token_account: Pubkey::new_unique(),
+        };
+        let token_data = |amount: u64| {
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            let account = spl_token::state::Account {
+                mint: nft.mint,
+                owner,
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            };
+            spl_token::state::Account::pack(account, &mut data).unwrap();
+            data
+        };
+        let token_program = spl_token::id();
+        let system_program = Pubkey::default();
+
+        let mut lamports = 0;
+        let mut data = token_data(1);
+        let held = AccountInfo::new(&nft.token_account, false, false, &mut lamports, &mut data, &token_program, false, 0);
+        assert!(holds_membership_nft(&nft, &owner, &held));
+        assert!(!holds_membership_nft(&nft, &Pubkey::new_unique(), &held));
+
+        let mut burned_lamports = 0;
+        let mut burned_data = token_data(0);
+        let burned = AccountInfo::new(
+            &nft.token_account,
+            false,
+            false,
+            &mut burned_lamports,
+            &mut burned_data,
+            &token_program,
+            false,
+            0,
+        );
+        assert!(!holds_membership_nft(&nft, &owner, &burned));
+
+        // Закрытый аккаунт возвращается системной программе без данных
+        let mut closed_lamports = 0;
+        let mut closed_data: Vec<u8> = vec![];
+        let closed = AccountInfo::new(
+            &nft.token_account,
+            false,
+            false,
+            &mut closed_lamports,
+            &mut closed_data,
+            &system_program,
+            false,
+            0,
+        );
+        assert!(!holds_membership_nft(&nft, &owner, &closed));
+
+        let friends: Vec<Pubkey> = (0..NFT_FRIEND_THRESHOLD).map(|_| Pubkey::new_unique()).collect();
+        let mut user_profile = profile_with_friends(owner, &friends);
+        user_profile.grant_membership(0);
+        user_profile.membership_nft = Some(nft);
+        assert!(user_profile.can_write_post(10));
+
+        assert!(!user_profile.reconcile_membership(true));
+        assert!(user_profile.reconcile_membership(false));
+        assert!(!user_profile.nft_owned);
+        assert_eq!(user_profile.membership_expires_at, 0);
+        assert!(!user_profile.can_write_post(10));
+        assert!(!user_profile.reconcile_membership(false));
+    }
+
+    #[test]
+    fn test_rank_by_overlap() {
+        let friends: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let me = profile_with_friends(Pubkey::new_unique(), &friends);
+
+        let (low, high, none) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let low_profile = profile_with_friends(low, &friends[..1]);
+        let high_profile = profile_with_friends(high, &friends[..3]);
+        let none_profile = profile_with_friends(none, &[Pubkey::new_unique()]);
+
+        let ranked = rank_by_overlap(&me, &[(low, &low_profile), (none, &none_profile), (high, &high_profile)]);
+        assert_eq!(ranked, vec![(high, 3), (low, 1), (none, 0)]);
+    }
+
+    #[test]
+    fn test_share_contact_between_friends() {
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let mut alice_profile = profile_with_friends(alice, &[bob]);
+        let mut bob_profile = profile_with_friends(bob, &[alice]);
+        let stranger = profile_with_friends(Pubkey::new_unique(), &[bob]);
+
+        alice_profile.set_private_contact(vec![1; MAX_CONTACT_BLOB_LEN]).unwrap();
+        assert_eq!(
+            alice_profile.set_private_contact(vec![1; MAX_CONTACT_BLOB_LEN + 1]),
+            Err(NetworkingError::ContactBlobTooLarge.into())
+        );
+
+        bob_profile.receive_shared_contact(&alice_profile, vec![2; 64]).unwrap();
+        bob_profile.receive_shared_contact(&alice_profile, vec![3; 64]).unwrap();
+        assert_eq!(bob_profile.shared_contacts, vec![(alice, vec![3; 64])]);
+
+        assert_eq!(
+            bob_profile.receive_shared_contact(&stranger, vec![4; 64]),
+            Err(NetworkingError::NotFriends.into())
+        );
+        assert_eq!(
+            bob_profile.receive_shared_contact(&alice_profile, vec![5; MAX_CONTACT_BLOB_LEN + 1]),
+            Err(NetworkingError::ContactBlobTooLarge.into())
+        );
+        assert_eq!(bob_profile.shared_contacts.len(), 1);
+    }
+
+    #[test]
+    fn test_identicon_seed_and_svg() {
+        let address = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(address, &[]);
+        let seed = user_profile.identicon_seed;
+        assert_eq!(seed, identicon_seed(&address));
+        assert_ne!(seed, identicon_seed(&Pubkey::new_unique()));
+
+        user_profile.name = "Renamed".to_string();
+        user_profile.set_theme(Theme::Dark as u8).unwrap();
+        assert_eq!(user_profile.identicon_seed, seed);
+
+        let svg = identicon_svg(seed);
+        assert_eq!(svg, identicon_svg(seed));
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<svg").count(), 1);
+        assert_eq!(svg.matches("<rect").count(), svg.matches("/>").count());
+
+        // Все клетки заполнены: 5 строк по 5 квадратов
+        assert_eq!(identicon_svg([0xff; 8]).matches("<rect").count(), 25);
+        assert_eq!(identicon_svg([0; 8]).matches("<rect").count(), 0);
+    }
+
+    #[test]
+    fn test_post_authors_consistent() {
+        let author = Pubkey::new_unique();
+        let mut post = Post::new(author, "Hello".to_string(), vec![], 0);
+        assert!(post.authors_consistent());
+
+        post.add_comment(Pubkey::new_unique(), "Hi".to_string()).unwrap();
+        assert!(post.authors_consistent());
+
+        post.add_comment(Pubkey::default(), "Ghost".to_string()).unwrap();
+        assert!(!post.authors_consistent());
+    }
+
+    #[test]
+    fn test_set_timezone() {
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        user_profile.set_timezone(5).unwrap();
+        assert_eq!(user_profile.timezone_offset, 5);
+        user_profile.set_timezone(MIN_TIMEZONE_OFFSET).unwrap();
+        user_profile.set_timezone(MAX_TIMEZONE_OFFSET).unwrap();
+
+        for offset in [MIN_TIMEZONE_OFFSET - 1, MAX_TIMEZONE_OFFSET + 1] {
+            assert_eq!(
+                user_profile.set_timezone(offset),
+                Err(NetworkingError::InvalidTimezone.into())
+            );
+        }
+        assert_eq!(user_profile.timezone_offset, MAX_TIMEZONE_OFFSET);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_error_log_roundtrip() {
+        use crate::client::parse_error_log;
+
+        assert_eq!(log_schema_version(), LOG_SCHEMA_VERSION);
+        for (code, _) in ERROR_TABLE {
+            let line = format_error_log(Some(3), &NetworkingError::from_code(*code).unwrap().into());
+            assert_eq!(parse_error_log(&line), Some((*code as u64, Some(3))));
+        }
+
+        let line = format_error_log(None, &ProgramError::MissingRequiredSignature);
+        assert!(line.starts_with("ERR v1 code="));
+        assert_eq!(
+            parse_error_log(&format!("Program log: {}", line)),
+            Some((u64::from(ProgramError::MissingRequiredSignature), None))
+        );
+        assert_eq!(
+            format_error_log(Some(4), &NetworkingError::PostNotFound.into()),
+            "ERR v1 code=2 ix=4 reason=\"Post not found\""
+        );
+
+        assert_eq!(parse_error_log("ERR v2 code=1 ix=0 reason=\"x\""), None);
+        assert_eq!(parse_error_log("Program log: hello"), None);
+        assert_eq!(parse_error_log("ERR v1 code=x ix=0 reason=\"x\""), None);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_every_instruction_logs_parsable_error() {
+        use crate::client::parse_error_log;
+        use ProfessionalNetworkingInstruction::*;
+
+        let key = Pubkey::new_unique();
+        let text = || "x".to_string();
+        // Варианты в порядке тегов; каждый падает на чужом пустом аккаунте
+        let instructions = vec![
+            CreateUserProfile { name: text(), bio: text(), profile_picture: text(), referred_by: None },
+            SendFriendRequest { friend_address: key },
+            AcceptFriendRequest { friend_address: key },
+            WritePost { content: text(), tags: vec![], attachments: vec![] },
+            AddComment { post_author: key, post_index: 0, content: text() },
+            InitializeConfig { oracles: vec![key] },
+            SetOracles { oracles: vec![key] },
+            AttestExternalIdentity { platform: 0, handle_hash: [0; 32] },
+            RemoveExternalIdentity { platform: 0 },
+            ClaimReferral,
+            RecordImpression { post_author: key, post_index: 0 },
+            SetTheme { theme: 0 },
+            QuoteComment { post_author: key, post_index: 0, comment_index: 0, commentary: None },
+            DeactivateProfile,
+            ReactivateProfile,
+            ReportUser { target: key, reason_code: 0 },
+            RenewMembership,
+            VerifyDomain { target: key, domain: text() },
+            AddSection { title: text(), body: text() },
+            UpdateSection { index: 0, title: text(), body: text() },
+            RemoveSection { index: 0 },
+            ReorderSections { new_order: vec![0] },
+            SetRetentionPolicy { max_retained_posts: 1 },
+            SetRepairsEnabled { enabled: true },
+            AdminRepairAccount { patch: vec![RepairOp::ZeroTail { from: 0 }] },
+            SnapshotProfile { timestamp: TEST_NOW },
+            ReconcileMembership { profile: key },
+            SetEncryptionKey { key: [1; 32] },
+            SetPrivateContact { blob: vec![1] },
+            ShareContactWith { peer: key, blob: vec![1] },
+            SetTimezone { offset: 0 },
+            SetAvailability { start: 9, end: 18 },
+            SetMembershipThreshold { threshold: 1 },
+            RegisterIndexer { indexer: key },
+            SetIndexerCheckpoint { last_nonce_seen: 1, last_slot: 1 },
+            RetireIndexer,
+            InitializeAnonymousBoard,
+            WriteAnonymousPost { content: text(), commitment: [0; 32] },
+            CommentAnonymousPost { post_index_on_board: 0, content: text() },
+            RevealAnonymousPost { post_index_on_board: 0, salt: [0; 32] },
+        ];
+        // Следующего тега нет — список покрывает все инструкции
+        assert!(ProfessionalNetworkingInstruction::try_from_slice(&[instructions.len() as u8]).is_err());
+
+        let program_id = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![];
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &other_owner, false, 0)];
+        for (tag, instruction) in instructions.iter().enumerate() {
+            assert_eq!(instruction.try_to_vec().unwrap()[0] as usize, tag);
+            let (result, logs) = run_logged(&program_id, &accounts, instruction);
+            let code = match result {
+                Err(ProgramError::Custom(code)) => code as u64,
+                Err(error) => u64::from(error),
+                Ok(()) => panic!("{:?} should fail", instruction),
+            };
+            let parsed: Vec<_> = logs.iter().filter_map(|line| parse_error_log(line)).collect();
+            assert_eq!(parsed, vec![(code, Some(tag as u8))], "{:?}", instruction);
+        }
+
+        install_syscalls();
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        assert_eq!(process_instruction(&program_id, &accounts, &[]), Err(ProgramError::InvalidInstructionData));
+        let logs = LOGS.with(|logs| logs.take());
+        let parsed: Vec<_> = logs.iter().filter_map(|line| parse_error_log(line)).collect();
+        assert_eq!(parsed, vec![(u64::from(ProgramError::InvalidInstructionData), None)]);
+    }
+
+    #[test]
+    fn test_busiest_window() {
+        let author = Pubkey::new_unique();
+        let mut user_profile = profile_with_friends(author, &[]);
+        assert_eq!(user_profile.busiest_window(&author, 3600), None);
+
+        let day = 24 * 60 * 60;
+        for timestamp in [5 * day, 100, 2 * day + 10, 2 * day + 50, 200, 2 * day + 3000, 2 * day + 3599] {
+            user_profile.add_post(author, "post".to_string(), vec![], vec![], timestamp);
+        }
+        assert_eq!(user_profile.busiest_window(&author, 3600), Some((2 * day + 10, 4)));
+        assert_eq!(user_profile.busiest_window(&author, 1), Some((100, 1)));
+        assert_eq!(user_profile.busiest_window(&author, 0), None);
+        assert_eq!(user_profile.busiest_window(&Pubkey::new_unique(), 3600), None);
+    }
+
+    #[test]
+    fn test_acceptance_rate() {
+        let alice = Pubkey::new_unique();
+        let mut alice_profile = profile_with_friends(alice, &[]);
+        assert_eq!(alice_profile.acceptance_rate(), 0.0);
+
+        let mut others: Vec<UserProfile> = (0..4)
+            .map(|_| profile_with_friends(Pubkey::new_unique(), &[]))
+            .collect();
+        for other in &others {
+            alice_profile.send_friend_request(other.address).unwrap();
+        }
+        assert_eq!(
+            alice_profile.send_friend_request(others[0].address),
+            Err(NetworkingError::AlreadyFriends.into())
+        );
+        assert_eq!(alice_profile.requests_sent.get(), 4);
+
+        others[0].link_friends(&mut alice_profile).unwrap();
+        others[1].link_friends(&mut alice_profile).unwrap();
+        // Повторное принятие не засчитывается
+        others[0].link_friends(&mut alice_profile).unwrap();
+        assert_eq!(alice_profile.requests_accepted.get(), 2);
+        assert_eq!(alice_profile.acceptance_rate(), 0.5);
+        assert_eq!(others[0].requests_accepted.get(), 0);
+    }
+
+    #[test]
+    fn test_account_borrow_guard() {
+        let owner = Pubkey::new_unique();
+        let (first_key, second_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut first_lamports, mut duplicate_lamports, mut second_lamports) = (0, 0, 0);
+        let (mut first_data, mut duplicate_data, mut second_data) = (vec![0; 8], vec![0; 8], vec![0; 8]);
+        let first = AccountInfo::new(&first_key, false, true, &mut first_lamports, &mut first_data, &owner, false, 0);
+        // Тот же ключ вторым AccountInfo, как при повторе в списке аккаунтов
+        let duplicate =
+            AccountInfo::new(&first_key, false, true, &mut duplicate_lamports, &mut duplicate_data, &owner, false, 0);
+        let second = AccountInfo::new(&second_key, false, true, &mut second_lamports, &mut second_data, &owner, false, 0);
+
+        let mut borrows = AccountBorrowGuard::default();
+        let first_borrow = borrows.borrow_mut_data(&first).unwrap();
+        assert_eq!(
+            borrows.borrow_mut_data(&duplicate).unwrap_err(),
+            NetworkingError::DuplicateAccount.into()
+        );
+
+        let held = second.try_borrow_data().unwrap();
+        assert_eq!(
+            borrows.borrow_mut_data(&second).unwrap_err(),
+            NetworkingError::BorrowConflict.into()
+        );
+        drop(held);
+        assert!(borrows.borrow_mut_data(&second).is_ok());
+        drop(first_borrow);
+    }
+
+    #[test]
+    fn test_estimate_growth_is_never_under_actual() {
+        let me = Pubkey::new_unique();
+        let size = |profile: &UserProfile| profile.try_to_vec().unwrap().len();
+
+        for n in [0usize, 1, 7, 64, 500] {
+            let mut profile = profile_with_friends(me, &[]);
+            for round in 0..2 {
+                let op = MutationKind::AddPost { content_len: n, tags: 2, attachments: 1 };
+                let (estimate, before) = (profile.estimate_growth(&op), size(&profile));
+                let tags = vec!["a".repeat(MAX_TAG_LEN), format!("t{}", round)];
+                let attachments = vec![Attachment {
+                    uri: format!("https://{}", "x".repeat(MAX_ATTACHMENT_URI_LEN - 8)),
+                    content_hash: [0; 32],
+                    mime_hint: 0,
+                }];
+                profile.add_post(me, "p".repeat(n), tags, attachments, 0);
+                assert!(size(&profile) - before <= estimate);
+
+                let op = MutationKind::AddQuotePost { commentary_len: n };
+                let (estimate, before) = (profile.estimate_growth(&op), size(&profile));
+                let kind = PostKind::Quote {
+                    source_post_author: me,
+                    source_post_index: 0,
+                    source_comment_index: 0,
+                    quoted_hash: [1; 32],
+                };
+                profile.add_quote_post(me, Some("q".repeat(n)), kind, 0);
+                assert!(size(&profile) - before <= estimate);
+
+                let op = MutationKind::AddComment { content_len: n };
+                let (estimate, before) = (profile.estimate_growth(&op), size(&profile));
+                profile.add_comment(me, 0, Pubkey::new_unique(), "c".repeat(n)).unwrap();
+                assert!(size(&profile) - before <= estimate);
+
+                let (estimate, before) = (profile.estimate_growth(&MutationKind::AddFriend), size(&profile));
+                profile.friends.insert(Pubkey::new_unique());
+                assert!(size(&profile) - before <= estimate);
+
+                let friend = profile_with_friends(Pubkey::new_unique(), &[me]);
+                profile.friends.insert(friend.address);
+                let op = MutationKind::ShareContact { blob_len: n };
+                let (estimate, before) = (profile.estimate_growth(&op), size(&profile));
+                profile.receive_shared_contact(&friend, vec![0; n.min(MAX_CONTACT_BLOB_LEN)]).unwrap();
+                assert!(size(&profile) - before <= estimate);
+            }
+        }
+
+        let mut profile = profile_with_friends(me, &[]);
+        let (estimate, before) = (profile.estimate_growth(&MutationKind::RecordMembershipNft), size(&profile));
+        profile.membership_nft = Some(MembershipNft { mint: me, token_account: me });
+        assert!(size(&profile) - before <= estimate);
+    }
+
+    #[test]
+    fn test_ensure_room_limits() {
+        let me = Pubkey::new_unique();
+        let mut profile = profile_with_friends(me, &[]);
+        let op = MutationKind::AddPost { content_len: 100, tags: 0, attachments: 0 };
+        let needed = profile.try_to_vec().unwrap().len() + profile.estimate_growth(&op);
+
+        assert_eq!(profile.ensure_room(&op, needed), Ok(()));
+        assert_eq!(
+            profile.ensure_room(&op, needed - 1),
+            Err(NetworkingError::AccountDataTooSmall.into())
+        );
+
+        profile.bio = "b".repeat(MAX_PROFILE_SIZE);
+        assert_eq!(
+            profile.ensure_room(&op, MAX_PROFILE_ACCOUNT_LEN),
+            Err(NetworkingError::ProfileSizeLimitReached.into())
+        );
+    }
+
+    #[test]
+    fn test_full_account_growth_errors_instead_of_panicking() {
+        let program_id = Pubkey::new_unique();
+        let viewer = Pubkey::new_unique();
+        let author = Pubkey::new_unique();
+        let mut author_profile = profile_with_friends(author, &[]);
+        author_profile.add_post(author, "hello".to_string(), vec![], vec![], 0);
+
+        // Данные аккаунта ровно по размеру профиля, места для роста нет
+        let (mut viewer_lamports, mut author_lamports) = (0, 0);
+        let mut viewer_data = vec![];
+        let mut author_data = author_profile.try_to_vec().unwrap();
+        let before = author_data.clone();
+        let accounts = [
+            AccountInfo::new(&viewer, true, false, &mut viewer_lamports, &mut viewer_data, &program_id, false, 0),
+            AccountInfo::new(&author, false, true, &mut author_lamports, &mut author_data, &program_id, false, 0),
+        ];
+        let report = ProfessionalNetworkingInstruction::ReportUser { target: author, reason_code: 1 };
+        let impression = ProfessionalNetworkingInstruction::RecordImpression { post_author: author, post_index: 0 };
+        for instruction in [report, impression] {
+            assert_eq!(
+                process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()),
+                Err(NetworkingError::AccountDataTooSmall.into())
+            );
+        }
+        assert_eq!(*accounts[1].try_borrow_data().unwrap(), &before[..]);
+    }
+
+    #[test]
+    fn test_unestimated_growth_respects_profile_size_limit() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut profile = profile_with_friends(user, &[]);
+        while profile.try_to_vec().unwrap().len() + 1100 < MAX_PROFILE_SIZE {
+            profile.add_post(user, "x".repeat(1000), vec![], vec![], 0);
+        }
+        profile.bio = "x".repeat(MAX_PROFILE_SIZE - profile.try_to_vec().unwrap().len() - 20);
+
+        // В аккаунте места хватает, ограничивает только MAX_PROFILE_SIZE
+        let mut lamports = 0;
+        let mut data = profile.try_to_vec().unwrap();
+        data.resize(MAX_PROFILE_SIZE + 4096, 0);
+        let before = data.clone();
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+
+        let add_section = ProfessionalNetworkingInstruction::AddSection { title: "Talks".to_string(), body: "y".repeat(100) };
+        let set_contact = ProfessionalNetworkingInstruction::SetPrivateContact { blob: vec![1; 100] };
+        for instruction in [add_section, set_contact] {
+            assert_eq!(run(&program_id, &accounts, &instruction), Err(NetworkingError::ProfileSizeLimitReached.into()));
+        }
+        assert_eq!(*accounts[0].try_borrow_data().unwrap(), &before[..]);
+    }
+
+    #[test]
+    fn test_repeat_accept_on_full_accounts_is_noop() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let (mut user_lamports, mut friend_lamports) = (0, 0);
+        let mut user_data = profile_with_friends(user, &[friend]).try_to_vec().unwrap();
+        let mut friend_data = profile_with_friends(friend, &[user]).try_to_vec().unwrap();
+        let accounts = [
+            AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &program_id, false, 0),
+            AccountInfo::new(&friend, false, true, &mut friend_lamports, &mut friend_data, &program_id, false, 0),
+        ];
+
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: friend };
+        assert_eq!(process_instruction(&program_id, &accounts, &accept.try_to_vec().unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_set_availability() {
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        assert_eq!(user_profile.available_hours, None);
+
+        user_profile.set_availability(9, 17).unwrap();
+        assert_eq!(user_profile.available_hours, Some((9, 17)));
+        user_profile.set_availability(22, 6).unwrap();
+
+        for (start, end) in [(24, 5), (5, 24), (255, 0)] {
+            assert_eq!(
+                user_profile.set_availability(start, end),
+                Err(NetworkingError::InvalidAvailability.into())
+            );
+        }
+        assert_eq!(user_profile.available_hours, Some((22, 6)));
+    }
+
+    #[test]
+    fn test_longest_path_from() {
+        let keys: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        // Цепочка 0-1-2-3 и треугольник 1-2-4
+        let profiles = [
+            profile_with_friends(keys[0], &[keys[1]]),
+            profile_with_friends(keys[1], &[keys[2], keys[4]]),
+            profile_with_friends(keys[2], &[keys[3], keys[4]]),
+            profile_with_friends(keys[3], &[]),
+            profile_with_friends(keys[4], &[]),
+        ];
+        let adjacency = build_adjacency(&profiles.iter().collect::<Vec<_>>());
+
+        // 0-1-4-2-3
+        assert_eq!(longest_path_from(&keys[0], &adjacency, 10), 4);
+        assert_eq!(longest_path_from(&keys[0], &adjacency, 2), 2);
+        assert_eq!(longest_path_from(&keys[3], &adjacency, 10), 4);
+        assert_eq!(longest_path_from(&Pubkey::new_unique(), &adjacency, 10), 0);
+    }
+
+    #[test]
+    fn test_unsigned_user_account_is_rejected() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let mut user_lamports = 0;
+        let mut user_data = profile_with_friends(user, &[]).try_to_vec().unwrap();
+        let mut friend_lamports = 0;
+        let mut friend_data = profile_with_friends(friend, &[user]).try_to_vec().unwrap();
+        let friend_before = friend_data.clone();
+        let accounts = [
+            AccountInfo::new(&user, false, true, &mut user_lamports, &mut user_data, &program_id, false, 0),
+            AccountInfo::new(&friend, false, true, &mut friend_lamports, &mut friend_data, &program_id, false, 0),
+        ];
+
+        let write_post = ProfessionalNetworkingInstruction::WritePost {
+            content: "Hello".to_string(),
+            tags: vec![],
+            attachments: vec![],
+        };
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: friend };
+        for instruction in [write_post, accept] {
+            assert_eq!(
+                process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+        }
+        assert_eq!(*accounts[1].try_borrow_data().unwrap(), &friend_before[..]);
+    }
+
+    #[test]
+    fn test_original_arms_require_signer() {
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let unsigned = [
+            ProfessionalNetworkingInstruction::CreateUserProfile {
+                name: "Alice".to_string(),
+                bio: String::new(),
+                profile_picture: String::new(),
+                referred_by: None,
+            },
+            ProfessionalNetworkingInstruction::SendFriendRequest { friend_address: friend },
+            ProfessionalNetworkingInstruction::AddComment {
+                post_author: friend,
+                post_index: 0,
+                content: "Hi".to_string(),
+            },
+        ];
+        for instruction in unsigned {
+            let mut lamports = 0;
+            let mut data = vec![0; 256];
+            let accounts = [AccountInfo::new(&user, false, true, &mut lamports, &mut data, &program_id, false, 0)];
+            assert_eq!(
+                process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+            assert!(accounts[0].try_borrow_data().unwrap().iter().all(|b| *b == 0));
+        }
+
+        // Принимающий подписал — профиль друга (без подписи) обновляется
+        let (mut user_lamports, mut friend_lamports) = (0, 0);
+        let mut user_data = profile_with_friends(user, &[]).try_to_vec().unwrap();
+        user_data.resize(512, 0);
+        let mut friend_data = profile_with_friends(friend, &[user]).try_to_vec().unwrap();
+        friend_data.resize(512, 0);
+        let accounts = [
+            AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &program_id, false, 0),
+            AccountInfo::new(&friend, false, true, &mut friend_lamports, &mut friend_data, &program_id, false, 0),
+        ];
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: friend };
+        assert_eq!(process_instruction(&program_id, &accounts, &accept.try_to_vec().unwrap()), Ok(()));
+        let friend_profile = UserProfile::load(&accounts[1].try_borrow_data().unwrap()).unwrap();
+        assert!(friend_profile.friends.contains(&user));
+        assert_eq!(friend_profile.requests_accepted.get(), 1);
+    }
+
+    #[test]
+    fn test_foreign_owned_profile_is_rejected() {
+        let program_id = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let friend = Pubkey::new_unique();
+        let (mut user_lamports, mut friend_lamports) = (0, 0);
+        let mut user_data = profile_with_friends(user, &[]).try_to_vec().unwrap();
+        user_data.resize(512, 0);
+        let mut friend_data = profile_with_friends(friend, &[user]).try_to_vec().unwrap();
+        friend_data.resize(512, 0);
+
+        let write_post = ProfessionalNetworkingInstruction::WritePost {
+            content: "Hello".to_string(),
+            tags: vec![],
+            attachments: vec![],
         }
         .try_to_vec()
         .unwrap();
-        let mut transaction = Transaction::new_with_payer(
-            &[Instruction::new_with_bytes(program_id, &add_comment_data, vec![user_account.pubkey()])],
-            Some(&payer.pubkey()),
+        let accept = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: friend }
+            .try_to_vec()
+            .unwrap();
+
+        {
+            let accounts =
+                [AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &other_program, false, 0)];
+            assert_eq!(process_instruction(&program_id, &accounts, &write_post), Err(ProgramError::IllegalOwner));
+        }
+
+        let accounts = [
+            AccountInfo::new(&user, true, true, &mut user_lamports, &mut user_data, &program_id, false, 0),
+            AccountInfo::new(&friend, false, true, &mut friend_lamports, &mut friend_data, &other_program, false, 0),
+        ];
+        assert_eq!(process_instruction(&program_id, &accounts, &accept), Err(ProgramError::IllegalOwner));
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let me = Pubkey::new_unique();
+        let day = 24 * 60 * 60;
+        let now = 100 * day;
+        let mut user_profile = profile_with_friends(me, &[]);
+        assert!(user_profile.is_stale(now, 30 * day));
+
+        user_profile.add_post(me, "old".to_string(), vec![], vec![], now - 60 * day);
+        assert!(user_profile.is_stale(now, 30 * day));
+
+        user_profile.add_post(me, "recent".to_string(), vec![], vec![], now - day);
+        assert_eq!(user_profile.last_activity(), Some(now - day));
+        assert!(!user_profile.is_stale(now, 30 * day));
+        assert!(user_profile.is_stale(now, day / 2));
+    }
+
+    #[test]
+    fn test_unchanged_settings_are_not_rewritten() {
+        let mut offset = 3i8;
+        assert!(!set_if_changed(&mut offset, 3));
+        assert!(set_if_changed(&mut offset, 4));
+        assert_eq!(offset, 4);
+
+        let mut user_profile = profile_with_friends(Pubkey::new_unique(), &[]);
+        assert_eq!(user_profile.set_timezone(5), Ok(true));
+        assert_eq!(user_profile.set_timezone(5), Ok(false));
+
+        let program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = profile_with_friends(user, &[]).try_to_vec().unwrap();
+        let before = data.clone();
+        let theme = UserProfile::load(&before).unwrap().theme;
+        let accounts = [AccountInfo::new(&user, true, true, &mut lamports, &mut data, &program_id, false, 0)];
+        let instruction = ProfessionalNetworkingInstruction::SetTheme { theme };
+        assert_eq!(process_instruction(&program_id, &accounts, &instruction.try_to_vec().unwrap()), Ok(()));
+        assert_eq!(*accounts[0].try_borrow_data().unwrap(), &before[..]);
+    }
+
+    #[test]
+    fn test_duplicate_attachment_uri_is_rejected() {
+        let attachments = vec![attachment("https://example.com/a.png"), attachment("https://example.com/a.png")];
+        assert_eq!(validate_attachments(&attachments), Err(NetworkingError::DuplicateAttachmentUri.into()));
+        assert!(validate_attachments(&attachments[..1]).is_ok());
+    }
+
+    #[test]
+    fn test_indexer_checkpoint_lifecycle() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let indexer = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let config_key = config_address(&program_id).0;
+        let checkpoint_key = indexer_checkpoint_address(&program_id, &indexer).0;
+
+        let mut checkpoint = IndexerCheckpoint::new(indexer);
+        checkpoint.advance(10, 100).unwrap();
+        let (mut admin_lamports, mut indexer_lamports, mut stranger_lamports) = (0, 0, 0);
+        let (mut config_lamports, mut checkpoint_lamports, mut recipient_lamports) = (0, 1_000, 5);
+        let (mut admin_data, mut indexer_data, mut stranger_data, mut recipient_data) = (vec![], vec![], vec![], vec![]);
+        let mut config_data = config_account_data(&NetworkConfig::new(admin, vec![]));
+        let mut checkpoint_data = checkpoint.try_to_vec().unwrap();
+        let admin_info = AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0);
+        let indexer_info = AccountInfo::new(&indexer, true, false, &mut indexer_lamports, &mut indexer_data, &program_id, false, 0);
+        let stranger_info =
+            AccountInfo::new(&stranger, true, false, &mut stranger_lamports, &mut stranger_data, &program_id, false, 0);
+        let config_info = AccountInfo::new(&config_key, false, false, &mut config_lamports, &mut config_data, &program_id, false, 0);
+        let checkpoint_info =
+            AccountInfo::new(&checkpoint_key, false, true, &mut checkpoint_lamports, &mut checkpoint_data, &program_id, false, 0);
+        let recipient_info =
+            AccountInfo::new(&recipient, false, true, &mut recipient_lamports, &mut recipient_data, &program_id, false, 0);
+        let register_by_stranger =
+            [stranger_info.clone(), config_info.clone(), checkpoint_info.clone(), stranger_info.clone(), recipient_info.clone()];
+        let by_stranger = [stranger_info.clone(), config_info.clone(), checkpoint_info.clone(), recipient_info.clone()];
+        let by_admin = [admin_info, config_info, checkpoint_info.clone(), recipient_info.clone()];
+        let stranger_writes = [stranger_info, checkpoint_info.clone()];
+        let indexer_writes = [indexer_info, checkpoint_info.clone()];
+        let set = |last_nonce_seen, last_slot| {
+            ProfessionalNetworkingInstruction::SetIndexerCheckpoint { last_nonce_seen, last_slot }.try_to_vec().unwrap()
+        };
+
+        // Регистрирует только админ
+        let register = ProfessionalNetworkingInstruction::RegisterIndexer { indexer: stranger };
+        assert_eq!(
+            process_instruction(&program_id, &register_by_stranger, &register.try_to_vec().unwrap()),
+            Err(NetworkingError::Unauthorized.into())
         );
-        transaction.sign(&[&payer, &user_account], recent_blockhash);
-        banks_client.process_transaction(transaction).await.unwrap();
 
-        let user_account_data = banks_client
-            .get_account(user_account.pubkey())
-            .await
-            .expect("account not found")
-            .expect("account empty");
-        let user_profile = UserProfile::try_from_slice(&user_account_data.data).unwrap();
+        // Писать отметку может только сам индексатор, и только вперёд
+        assert_eq!(process_instruction(&program_id, &stranger_writes, &set(11, 101)), Err(NetworkingError::Unauthorized.into()));
+        for (nonce, slot) in [(9, 101), (11, 99)] {
+            assert_eq!(
+                process_instruction(&program_id, &indexer_writes, &set(nonce, slot)),
+                Err(NetworkingError::CheckpointRegression.into())
+            );
+        }
+        assert_eq!(process_instruction(&program_id, &indexer_writes, &set(10, 100)), Ok(()));
+        assert_eq!(process_instruction(&program_id, &indexer_writes, &set(12, 150)), Ok(()));
+        let stored = IndexerCheckpoint::try_from_slice(&checkpoint_info.try_borrow_data().unwrap()).unwrap();
+        assert_eq!((stored.last_nonce_seen, stored.last_slot), (12, 150));
 
-        let post_with_comments = user_profile.get_post_with_comments(&post_author, post_index).unwrap();
-        assert_eq!(post_with_comments.comments.len(), 1);
-        assert_eq!(post_with_comments.comments[0].content, comment_content);
+        // Закрытие возвращает аренду и делает отметку недоступной
+        let retire = ProfessionalNetworkingInstruction::RetireIndexer.try_to_vec().unwrap();
+        assert_eq!(process_instruction(&program_id, &by_stranger, &retire), Err(NetworkingError::Unauthorized.into()));
+        assert_eq!(process_instruction(&program_id, &by_admin, &retire), Ok(()));
+        assert_eq!((checkpoint_info.lamports(), recipient_info.lamports()), (0, 1_005));
+        assert!(checkpoint_info.try_borrow_data().unwrap().iter().all(|byte| *byte == 0));
+        assert_eq!(process_instruction(&program_id, &indexer_writes, &set(13, 160)), Err(ProgramError::UninitializedAccount));
+    }
+
+
+    fn board_account_data(board: &AnonymousBoard) -> Vec<u8> {
+        let mut data = board.try_to_vec().unwrap();
+        data.resize(AnonymousBoard::LEN, 0);
+        data
     }
 
+    #[test]
+    fn test_anonymous_post_comment_and_reveal() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let poster = Pubkey::new_unique();
+        let author = Pubkey::new_unique();
+        let commenter = Pubkey::new_unique();
+        let board_key = anonymous_board_address(&program_id).0;
+        let config_key = config_address(&program_id).0;
+        let salt = [42; 32];
+
+        let (mut poster_lamports, mut author_lamports, mut commenter_lamports) = (0, 0, 0);
+        let (mut board_lamports, mut config_lamports) = (0, 0);
+        let (mut poster_data, mut author_data) = (vec![], vec![]);
+        let mut commenter_data = profile_account_data(&profile_with_friends(commenter, &[]));
+        let mut board_data = board_account_data(&AnonymousBoard::new());
+        let mut config_data = config_account_data(&NetworkConfig::new(admin, vec![]));
+        let poster_info = AccountInfo::new(&poster, true, false, &mut poster_lamports, &mut poster_data, &program_id, false, 0);
+        let author_info = AccountInfo::new(&author, true, false, &mut author_lamports, &mut author_data, &program_id, false, 0);
+        let commenter_info =
+            AccountInfo::new(&commenter, true, false, &mut commenter_lamports, &mut commenter_data, &program_id, false, 0);
+        let board_info = AccountInfo::new(&board_key, false, true, &mut board_lamports, &mut board_data, &program_id, false, 0);
+        let config_info = AccountInfo::new(&config_key, false, false, &mut config_lamports, &mut config_data, &program_id, false, 0);
+        let initialize_by_poster = [poster_info.clone(), config_info, board_info.clone(), poster_info.clone(), poster_info.clone()];
+        let by_poster = [poster_info, board_info.clone()];
+        let by_author = [author_info, board_info.clone()];
+        let by_commenter = [commenter_info.clone(), board_info.clone()];
+        let board = || AnonymousBoard::deserialize(&mut &board_info.try_borrow_data().unwrap()[..]).unwrap();
+        let reveal = |salt| ProfessionalNetworkingInstruction::RevealAnonymousPost { post_index_on_board: 0, salt };
+
+        // Доску создаёт только админ
+        let initialize = ProfessionalNetworkingInstruction::InitializeAnonymousBoard;
+        assert_eq!(run(&program_id, &initialize_by_poster, &initialize), Err(NetworkingError::Unauthorized.into()));
+
+        // Пост с одноразового ключа: автором записана доска
+        let write = ProfessionalNetworkingInstruction::WriteAnonymousPost {
+            content: "Is 90k fair for a senior role?".to_string(),
+            commitment: anonymous_commitment(&author, &salt),
+        };
+        let (result, logs) = run_logged(&program_id, &by_poster, &write);
+        assert_eq!(result, Ok(()));
+        assert!(logs.contains(&"AnonymousPostCreated: index=0".to_string()));
+        assert_eq!(board().posts[0].author, board_key);
+        assert_eq!(board().posts[0].timestamp, TEST_NOW);
+
+        let comment = ProfessionalNetworkingInstruction::CommentAnonymousPost { post_index_on_board: 0, content: "Low".to_string() };
+        assert_eq!(run(&program_id, &by_commenter, &comment), Ok(()));
+        assert_eq!(board().posts[0].comments[0].author, commenter);
+        // Комментировать может только профиль программы
+        assert_eq!(run(&program_id, &by_poster, &comment), Err(NetworkingError::ProfileNotInitialized.into()));
+
+        // Неверная соль и чужой ключ с верной солью не раскрывают пост
+        assert_eq!(run(&program_id, &by_author, &reveal([7; 32])), Err(NetworkingError::RevealMismatch.into()));
+        assert_eq!(run(&program_id, &by_commenter, &reveal(salt)), Err(NetworkingError::RevealMismatch.into()));
+        assert_eq!(board().posts[0].author, board_key);
+
+        assert_eq!(run(&program_id, &by_author, &reveal(salt)), Ok(()));
+        assert_eq!(board().posts[0].author, author);
+        assert_eq!(run(&program_id, &by_author, &reveal(salt)), Err(NetworkingError::AlreadyRevealed.into()));
+    }
+
+    #[test]
+    fn test_anonymous_board_storage_is_bounded() {
+        let sentinel = Pubkey::new_unique();
+        let mut board = AnonymousBoard::new();
+
+        for timestamp in 0..MAX_BOARD_POSTS as i64 + 2 {
+            let index = board.post(sentinel, "q".repeat(MAX_ANONYMOUS_TEXT_LEN), [1; 32], timestamp).unwrap();
+            assert_eq!(index, timestamp as u64);
+            for _ in 0..MAX_BOARD_COMMENTS_PER_POST {
+                board.comment(index, Pubkey::new_unique(), "c".repeat(MAX_ANONYMOUS_TEXT_LEN)).unwrap();
+            }
+            assert_eq!(
+                board.comment(index, Pubkey::new_unique(), "c".to_string()),
+                Err(NetworkingError::CommentLimitReached.into())
+            );
+        }
+        // Самые старые вытеснены, индексы остальных не сдвинулись
+        assert_eq!(board.posts.len(), MAX_BOARD_POSTS);
+        assert_eq!(board.posts[0].index, 2);
+        assert_eq!(board.comment(0, Pubkey::new_unique(), "late".to_string()), Err(NetworkingError::PostNotFound.into()));
+        assert!(board.try_to_vec().unwrap().len() <= AnonymousBoard::LEN);
+
+        let too_long = "q".repeat(MAX_ANONYMOUS_TEXT_LEN + 1);
+        assert_eq!(board.post(sentinel, too_long.clone(), [1; 32], 0), Err(NetworkingError::AnonymousTextTooLong.into()));
+        assert_eq!(board.comment(2, Pubkey::new_unique(), too_long), Err(NetworkingError::AnonymousTextTooLong.into()));
+    }
+
+
+    #[test]
+    fn test_mutual_accepts_in_one_transaction() {
+        let program_id = Pubkey::new_unique();
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let (mut alice_lamports, mut bob_lamports) = (0, 0);
+        let mut alice_data = profile_account_data(&profile_with_friends(alice, &[]));
+        let mut bob_data = profile_account_data(&profile_with_friends(bob, &[]));
+        let alice_info = AccountInfo::new(&alice, true, true, &mut alice_lamports, &mut alice_data, &program_id, false, 0);
+        let bob_info = AccountInfo::new(&bob, true, true, &mut bob_lamports, &mut bob_data, &program_id, false, 0);
+        let by_alice = [alice_info.clone(), bob_info.clone()];
+        let by_bob = [bob_info.clone(), alice_info.clone()];
+
+        let send = ProfessionalNetworkingInstruction::SendFriendRequest { friend_address: bob };
+        assert_eq!(run(&program_id, &by_alice[..1], &send), Ok(()));
+
+        // Обе стороны приняли в одной транзакции: инструкции выполняются подряд над теми же данными
+        let bob_accepts = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: alice };
+        let alice_accepts = ProfessionalNetworkingInstruction::AcceptFriendRequest { friend_address: bob };
+        let (result, logs) = run_logged(&program_id, &by_bob, &bob_accepts);
+        assert_eq!(result, Ok(()));
+        assert!(!logs.iter().any(|line| line.contains("AlreadyFriends")));
+        let (result, logs) = run_logged(&program_id, &by_alice, &alice_accepts);
+        assert_eq!(result, Ok(()));
+        assert!(logs.iter().any(|line| line.contains("AlreadyFriends")));
+
+        let check = |alice_info: &AccountInfo, bob_info: &AccountInfo| {
+            let alice_profile = UserProfile::load(&alice_info.try_borrow_data().unwrap()).unwrap();
+            let bob_profile = UserProfile::load(&bob_info.try_borrow_data().unwrap()).unwrap();
+            assert_eq!(alice_profile.friends.iter().collect::<Vec<_>>(), vec![&bob]);
+            assert_eq!(bob_profile.friends.iter().collect::<Vec<_>>(), vec![&alice]);
+            assert_eq!(alice_profile.requests_sent.get(), 1);
+            assert_eq!(alice_profile.requests_accepted.get(), 1);
+            assert_eq!(bob_profile.requests_accepted.get(), 0);
+        };
+        check(&alice_info, &bob_info);
+
+        // Третье принятие тоже проходит и ничего не меняет
+        let (result, logs) = run_logged(&program_id, &by_bob, &bob_accepts);
+        assert_eq!(result, Ok(()));
+        assert!(logs.iter().any(|line| line.contains("AlreadyFriends")));
+        check(&alice_info, &bob_info);
+    }
 }